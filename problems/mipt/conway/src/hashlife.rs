@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use crate::{Cell, Grid};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Index of a node in `Hashlife::nodes`.
+type NodeId = usize;
+
+/// State of a single cell. Cells outside of the grid are walls, which are never
+/// alive, so the grid evolves as if it were surrounded by dead cells.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum State {
+    Dead,
+    Alive,
+    Wall,
+}
+
+const DEAD: NodeId = 0;
+const ALIVE: NodeId = 1;
+const WALL: NodeId = 2;
+
+enum Node {
+    Leaf(State),
+    /// Square of 2^level × 2^level cells split into quadrants of the level below.
+    Inner {
+        level: u32,
+        /// North-west, north-east, south-west and south-east quadrants.
+        children: [NodeId; 4],
+        population: u64,
+    },
+}
+
+/// Game of Life on a quadtree where equal squares are the same node, so the
+/// future of every square is computed only once.
+pub struct Hashlife {
+    rows: usize,
+    cols: usize,
+    nodes: Vec<Node>,
+    /// Canonical node for every combination of quadrants.
+    canonical: HashMap<[NodeId; 4], NodeId>,
+    /// Centre of a node advanced by 2^j generations for every (node, j).
+    results: HashMap<(NodeId, u32), NodeId>,
+    /// Node of walls only for every level.
+    walls: Vec<NodeId>,
+    /// Square of the smallest level covering the grid, aligned to its top-left
+    /// corner, at least of level 1.
+    root: NodeId,
+}
+
+impl Hashlife {
+    pub fn new(grid: &Grid<Cell>) -> Self {
+        let (rows, cols) = grid.size();
+        let mut hashlife = Self {
+            rows,
+            cols,
+            nodes: vec![
+                Node::Leaf(State::Dead),
+                Node::Leaf(State::Alive),
+                Node::Leaf(State::Wall),
+            ],
+            canonical: HashMap::new(),
+            results: HashMap::new(),
+            walls: vec![WALL],
+            root: WALL,
+        };
+        let level = rows.max(cols).next_power_of_two().trailing_zeros().max(1);
+        hashlife.root = hashlife.build(grid, level, 0, 0);
+        hashlife
+    }
+
+    /// Advances the grid by 2^k generations.
+    pub fn step_pow2(&mut self, k: u32) {
+        let level = self.level(self.root);
+        let mut node = self.root;
+        // Walls around the grid keep it centred, and the future of a node
+        // spans 2^(level - 2) generations at most.
+        while self.level(node) < (level + 1).max(k + 2) {
+            node = self.expand(node);
+        }
+        node = self.advance(node, k);
+        while self.level(node) > level {
+            node = self.center(node);
+        }
+        self.root = node;
+    }
+
+    /// Writes the current generation into `grid`, which must be of the same size.
+    pub fn write(&self, grid: &mut Grid<Cell>) {
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                grid.set(Cell::Dead, row, col);
+            }
+        }
+        self.write_node(grid, self.root, 0, 0);
+    }
+
+    ////////////////////////////////////////////////////////////////////////////
+
+    fn level(&self, node: NodeId) -> u32 {
+        match self.nodes[node] {
+            Node::Leaf(_) => 0,
+            Node::Inner { level, .. } => level,
+        }
+    }
+
+    fn children(&self, node: NodeId) -> [NodeId; 4] {
+        match self.nodes[node] {
+            Node::Leaf(_) => panic!("leaf has no children"),
+            Node::Inner { children, .. } => children,
+        }
+    }
+
+    fn population(&self, node: NodeId) -> u64 {
+        match self.nodes[node] {
+            Node::Leaf(state) => u64::from(state == State::Alive),
+            Node::Inner { population, .. } => population,
+        }
+    }
+
+    fn join(&mut self, children: [NodeId; 4]) -> NodeId {
+        if let Some(&node) = self.canonical.get(&children) {
+            return node;
+        }
+        let node = self.nodes.len();
+        self.nodes.push(Node::Inner {
+            level: self.level(children[0]) + 1,
+            children,
+            population: children.iter().map(|&child| self.population(child)).sum(),
+        });
+        self.canonical.insert(children, node);
+        node
+    }
+
+    fn wall(&mut self, level: u32) -> NodeId {
+        while self.walls.len() <= level as usize {
+            let wall = *self.walls.last().unwrap();
+            let node = self.join([wall; 4]);
+            self.walls.push(node);
+        }
+        self.walls[level as usize]
+    }
+
+    fn build(&mut self, grid: &Grid<Cell>, level: u32, row: usize, col: usize) -> NodeId {
+        if row >= self.rows || col >= self.cols {
+            return self.wall(level);
+        }
+        if level == 0 {
+            return match grid.get(row, col) {
+                Cell::Dead => DEAD,
+                Cell::Alive => ALIVE,
+            };
+        }
+        let half = 1 << (level - 1);
+        let children = [(0, 0), (0, half), (half, 0), (half, half)]
+            .map(|(dr, dc)| self.build(grid, level - 1, row + dr, col + dc));
+        self.join(children)
+    }
+
+    fn write_node(&self, grid: &mut Grid<Cell>, node: NodeId, row: usize, col: usize) {
+        if self.population(node) == 0 {
+            return;
+        }
+        match self.nodes[node] {
+            Node::Leaf(_) => grid.set(Cell::Alive, row, col),
+            Node::Inner {
+                level, children, ..
+            } => {
+                let half = 1 << (level - 1);
+                let offsets = [(0, 0), (0, half), (half, 0), (half, half)];
+                for (child, (dr, dc)) in children.into_iter().zip(offsets) {
+                    self.write_node(grid, child, row + dr, col + dc);
+                }
+            }
+        }
+    }
+
+    /// Surrounds `node` with walls into a node of the level above, with `node`
+    /// in the centre.
+    fn expand(&mut self, node: NodeId) -> NodeId {
+        let wall = self.wall(self.level(node) - 1);
+        let [nw, ne, sw, se] = self.children(node);
+        let children = [
+            [wall, wall, wall, nw],
+            [wall, wall, ne, wall],
+            [wall, sw, wall, wall],
+            [se, wall, wall, wall],
+        ]
+        .map(|children| self.join(children));
+        self.join(children)
+    }
+
+    /// Returns the centre of `node` of the level below.
+    fn center(&mut self, node: NodeId) -> NodeId {
+        let [nw, ne, sw, se] = self.children(node).map(|child| self.children(child));
+        self.join([nw[3], ne[2], sw[1], se[0]])
+    }
+
+    /// Returns the centre of `node` of level `k >= 2`, advanced by 2^j generations,
+    /// where `j <= k - 2`.
+    fn advance(&mut self, node: NodeId, j: u32) -> NodeId {
+        if let Some(&result) = self.results.get(&(node, j)) {
+            return result;
+        }
+        let result = match self.level(node) {
+            2 => self.advance_base(node),
+            level => self.advance_inner(node, level, j),
+        };
+        self.results.insert((node, j), result);
+        result
+    }
+
+    /// Same as `advance` for `level > 2`: the node is split into nine overlapping
+    /// squares of the level below, whose centres are combined into four squares
+    /// that are advanced again.
+    fn advance_inner(&mut self, node: NodeId, level: u32, j: u32) -> NodeId {
+        let [nw, ne, sw, se] = self.children(node);
+        let [nwc, nec, swc, sec] = [nw, ne, sw, se].map(|child| self.children(child));
+        let n01 = self.join([nwc[1], nec[0], nwc[3], nec[2]]);
+        let n10 = self.join([nwc[2], nwc[3], swc[0], swc[1]]);
+        let n11 = self.join([nwc[3], nec[2], swc[1], sec[0]]);
+        let n12 = self.join([nec[2], nec[3], sec[0], sec[1]]);
+        let n21 = self.join([swc[1], sec[0], swc[3], sec[2]]);
+        let nine = [nw, n01, ne, n10, n11, n12, sw, n21, se];
+
+        // At full speed, both halves of the way are taken by the recursion,
+        // otherwise only the second one.
+        let full = j == level - 2;
+        let r = nine.map(|node| {
+            if full {
+                self.advance(node, j - 1)
+            } else {
+                self.center(node)
+            }
+        });
+        let quadrants = [
+            [r[0], r[1], r[3], r[4]],
+            [r[1], r[2], r[4], r[5]],
+            [r[3], r[4], r[6], r[7]],
+            [r[4], r[5], r[7], r[8]],
+        ];
+        let children = quadrants.map(|children| {
+            let node = self.join(children);
+            self.advance(node, if full { j - 1 } else { j })
+        });
+        self.join(children)
+    }
+
+    /// Returns the centre 2×2 of a 4×4 `node` after a generation.
+    fn advance_base(&mut self, node: NodeId) -> NodeId {
+        let mut cells = [[State::Dead; 4]; 4];
+        for (i, child) in self.children(node).into_iter().enumerate() {
+            for (j, leaf) in self.children(child).into_iter().enumerate() {
+                if let Node::Leaf(state) = self.nodes[leaf] {
+                    cells[i / 2 * 2 + j / 2][i % 2 * 2 + j % 2] = state;
+                }
+            }
+        }
+        let next = |row: usize, col: usize| {
+            let alive = (row - 1..=row + 1)
+                .flat_map(|r| (col - 1..=col + 1).map(move |c| (r, c)))
+                .filter(|&(r, c)| (r, c) != (row, col) && cells[r][c] == State::Alive)
+                .count();
+            match (cells[row][col], alive) {
+                (State::Wall, _) => WALL,
+                (State::Alive, 2) | (_, 3) => ALIVE,
+                _ => DEAD,
+            }
+        };
+        self.join([next(1, 1), next(1, 2), next(2, 1), next(2, 2)])
+    }
+}
@@ -1,5 +1,49 @@
 #![forbid(unsafe_code)]
 
+mod hashlife;
+mod universe;
+
+use hashlife::Hashlife;
+
+pub use universe::{Bounds, Universe};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// How the edges of a `Grid` are joined.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Topology {
+    /// There are no cells beyond the edges.
+    #[default]
+    Dead,
+    /// Opposite edges are joined, so the grid repeats in both directions.
+    Torus,
+    /// Left and right edges are joined as in `Torus`, top and bottom ones are
+    /// joined flipped, so a cell leaving at the bottom of column `c` enters at
+    /// the top of column `cols - 1 - c`.
+    KleinBottle,
+    /// The edges reflect the grid, so cells beyond an edge mirror the cells
+    /// on it.
+    Mirror,
+}
+
+impl Topology {
+    /// Maps a cell that may lie beyond the edges of a `rows` × `cols` grid
+    /// to the cell of the grid that stands for it.
+    fn wrap(self, row: isize, col: isize, rows: usize, cols: usize) -> Option<(usize, usize)> {
+        let (rows, cols) = (rows as isize, cols as isize);
+        let inside = |value: isize, size: isize| (0..size).contains(&value);
+        let (row, col) = match self {
+            _ if inside(row, rows) && inside(col, cols) => (row, col),
+            Topology::Dead => return None,
+            Topology::Torus => (row.rem_euclid(rows), col.rem_euclid(cols)),
+            Topology::KleinBottle if inside(row, rows) => (row, col.rem_euclid(cols)),
+            Topology::KleinBottle => (row.rem_euclid(rows), cols - 1 - col.rem_euclid(cols)),
+            Topology::Mirror => (row.clamp(0, rows - 1), col.clamp(0, cols - 1)),
+        };
+        Some((row as usize, col as usize))
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[derive(Clone, PartialEq, Eq)]
@@ -7,6 +51,7 @@ pub struct Grid<T> {
     rows: usize,
     cols: usize,
     grid: Vec<T>,
+    topology: Topology,
 }
 
 impl<T: Clone + Default> Grid<T> {
@@ -15,6 +60,7 @@ impl<T: Clone + Default> Grid<T> {
             rows,
             cols,
             grid: vec![T::default(); &rows * &cols],
+            topology: Topology::Dead,
         }
     }
 
@@ -23,9 +69,19 @@ impl<T: Clone + Default> Grid<T> {
             rows,
             cols,
             grid: grid.to_vec(),
+            topology: Topology::Dead,
         }
     }
 
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
     pub fn size(&self) -> (usize, usize) {
         (self.rows, self.cols)
     }
@@ -38,6 +94,9 @@ impl<T: Clone + Default> Grid<T> {
         self.grid[row * self.cols + col] = value;
     }
 
+    /// Returns the cells around (`row`, `col`) joined by the topology. On a grid
+    /// narrower than three cells, or at a `Topology::Mirror` edge, the same cell,
+    /// including the cell itself, may occur several times.
     pub fn neighbours(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
         #[rustfmt::skip]
         let offsets = vec![
@@ -52,8 +111,8 @@ impl<T: Clone + Default> Grid<T> {
             let nx = row as isize + ox;
             let ny = col as isize + oy;
             
-            if nx >= 0 && ny >= 0 && nx < self.rows as isize && ny < self.cols as isize {
-                neighbours.push((nx as usize, ny as usize));
+            if let Some(neighbour) = self.topology.wrap(nx, ny, self.rows, self.cols) {
+                neighbours.push(neighbour);
             }
         }
         
@@ -77,14 +136,52 @@ impl Default for Cell {
 
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(PartialEq, Eq)]
+/// How `GameOfLife` computes generations.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Backend {
+    /// Every cell of the grid is updated each generation.
+    Naive,
+    /// Quadtree with memoised futures of its nodes, which advances repetitive
+    /// patterns by 2^k generations in time that grows slowly with `k`.
+    ///
+    /// Nodes and their futures are never freed, so memory grows with every new
+    /// pattern the game passes through.
+    Hashlife,
+}
+
 pub struct GameOfLife {
     grid: Grid<Cell>,
+    hashlife: Option<Hashlife>,
+}
+
+impl PartialEq for GameOfLife {
+    fn eq(&self, other: &Self) -> bool {
+        self.grid == other.grid
+    }
 }
 
+impl Eq for GameOfLife {}
+
 impl GameOfLife {
     pub fn from_grid(grid: Grid<Cell>) -> Self {
-        Self { grid }
+        Self::with_backend(grid, Backend::Naive)
+    }
+
+    /// Panics if `backend` is `Backend::Hashlife` and the topology of `grid`
+    /// is not `Topology::Dead`.
+    pub fn with_backend(grid: Grid<Cell>, backend: Backend) -> Self {
+        let hashlife = match backend {
+            Backend::Naive => None,
+            Backend::Hashlife => {
+                assert_eq!(
+                    grid.topology(),
+                    Topology::Dead,
+                    "Hashlife supports only dead edges"
+                );
+                Some(Hashlife::new(&grid))
+            }
+        };
+        Self { grid, hashlife }
     }
 
     pub fn get_grid(&self) -> &Grid<Cell> {
@@ -92,6 +189,29 @@ impl GameOfLife {
     }
 
     pub fn step(&mut self) {
+        self.step_pow2(0);
+    }
+
+    /// Advances the game by 2^k generations. With `Backend::Hashlife` this
+    /// takes a single pass over the grid, with `Backend::Naive` 2^k of them.
+    ///
+    /// Panics if `k >= 64`, since the number of generations must fit into `u64`.
+    pub fn step_pow2(&mut self, k: u32) {
+        assert!(k < u64::BITS, "cannot advance by 2^{} generations", k);
+        match self.hashlife.as_mut() {
+            Some(hashlife) => {
+                hashlife.step_pow2(k);
+                hashlife.write(&mut self.grid);
+            }
+            None => {
+                for _ in 0..1u64 << k {
+                    self.step_naive();
+                }
+            }
+        }
+    }
+
+    fn step_naive(&mut self) {
         let mut new_grid = self.grid.clone();
         
         for row in 0..self.grid.rows {
@@ -0,0 +1,131 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Cell, Grid};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Rectangle of cells from `top` to `bottom` rows and from `left` to `right`
+/// columns, inclusive.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Bounds {
+    pub top: i64,
+    pub left: i64,
+    pub bottom: i64,
+    pub right: i64,
+}
+
+impl Bounds {
+    pub fn contains(&self, row: i64, col: i64) -> bool {
+        (self.top..=self.bottom).contains(&row) && (self.left..=self.right).contains(&col)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Game of Life on an unbounded plane, which keeps only coordinates of live
+/// cells, so patterns can grow and travel without limit.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub struct Universe {
+    alive: HashSet<(i64, i64)>,
+}
+
+impl Universe {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Places live cells of `grid` so that its top-left corner is at (`top`, `left`).
+    pub fn from_grid(grid: &Grid<Cell>, top: i64, left: i64) -> Self {
+        let (rows, cols) = grid.size();
+        let mut universe = Self::new();
+        for row in 0..rows {
+            for col in 0..cols {
+                universe.set(*grid.get(row, col), top + row as i64, left + col as i64);
+            }
+        }
+        universe
+    }
+
+    /// Returns the window of `rows` × `cols` cells with the top-left corner
+    /// at (`top`, `left`).
+    pub fn to_grid(&self, top: i64, left: i64, rows: usize, cols: usize) -> Grid<Cell> {
+        let mut grid = Grid::new(rows, cols);
+        for &(row, col) in &self.alive {
+            let (row, col) = (row - top, col - left);
+            if (0..rows as i64).contains(&row) && (0..cols as i64).contains(&col) {
+                grid.set(Cell::Alive, row as usize, col as usize);
+            }
+        }
+        grid
+    }
+
+    pub fn get(&self, row: i64, col: i64) -> Cell {
+        if self.alive.contains(&(row, col)) {
+            Cell::Alive
+        } else {
+            Cell::Dead
+        }
+    }
+
+    pub fn set(&mut self, value: Cell, row: i64, col: i64) {
+        match value {
+            Cell::Alive => self.alive.insert((row, col)),
+            Cell::Dead => self.alive.remove(&(row, col)),
+        };
+    }
+
+    /// Number of live cells.
+    pub fn population(&self) -> usize {
+        self.alive.len()
+    }
+
+    /// Coordinates of live cells in arbitrary order.
+    pub fn cells(&self) -> impl Iterator<Item = (i64, i64)> + '_ {
+        self.alive.iter().copied()
+    }
+
+    /// Coordinates of live cells within `bounds` in arbitrary order.
+    pub fn cells_in(&self, bounds: Bounds) -> impl Iterator<Item = (i64, i64)> + '_ {
+        self.cells()
+            .filter(move |&(row, col)| bounds.contains(row, col))
+    }
+
+    /// Smallest rectangle containing all live cells, `None` if there are none.
+    pub fn bounding_box(&self) -> Option<Bounds> {
+        let mut cells = self.cells();
+        let (row, col) = cells.next()?;
+        let bounds = Bounds {
+            top: row,
+            left: col,
+            bottom: row,
+            right: col,
+        };
+        Some(cells.fold(bounds, |bounds, (row, col)| Bounds {
+            top: bounds.top.min(row),
+            left: bounds.left.min(col),
+            bottom: bounds.bottom.max(row),
+            right: bounds.right.max(col),
+        }))
+    }
+
+    pub fn neighbours(row: i64, col: i64) -> impl Iterator<Item = (i64, i64)> {
+        (-1..=1)
+            .flat_map(|dr| (-1..=1).map(move |dc| (dr, dc)))
+            .filter(|&offset| offset != (0, 0))
+            .map(move |(dr, dc)| (row + dr, col + dc))
+    }
+
+    pub fn step(&mut self) {
+        let mut counts = HashMap::<(i64, i64), u8>::new();
+        for &(row, col) in &self.alive {
+            for neighbour in Self::neighbours(row, col) {
+                *counts.entry(neighbour).or_default() += 1;
+            }
+        }
+        self.alive = counts
+            .into_iter()
+            .filter(|&(cell, count)| count == 3 || count == 2 && self.alive.contains(&cell))
+            .map(|(cell, _)| cell)
+            .collect();
+    }
+}
@@ -1,4 +1,5 @@
-use conway::{Cell, GameOfLife, Grid};
+use conway::{Backend, Bounds, Cell, GameOfLife, Grid, Topology, Universe};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 fn get_grid(grid: Vec<Vec<u8>>) -> Grid<Cell> {
     let rows = grid.len();
@@ -188,3 +189,370 @@ fn glider() {
     game.step();
     assert!(game.get_grid() == &grid7);
 }
+
+fn random_grid(rng: &mut StdRng, rows: usize, cols: usize) -> Grid<Cell> {
+    let density = rng.gen_range(0.1..0.6);
+    let grid: Vec<Cell> = (0..rows * cols)
+        .map(|_| {
+            if rng.gen_bool(density) {
+                Cell::Alive
+            } else {
+                Cell::Dead
+            }
+        })
+        .collect();
+    Grid::from_slice(grid.as_slice(), rows, cols)
+}
+
+#[test]
+fn hashlife_glider() {
+    let grid = get_grid(vec![
+        vec![0, 1, 0, 0, 0, 0],
+        vec![0, 0, 1, 0, 0, 0],
+        vec![1, 1, 1, 0, 0, 0],
+        vec![0, 0, 0, 0, 0, 0],
+        vec![0, 0, 0, 0, 1, 1],
+        vec![0, 0, 0, 0, 1, 1],
+    ]);
+    let mut naive = GameOfLife::from_grid(grid.clone());
+    let mut hashlife = GameOfLife::with_backend(grid, Backend::Hashlife);
+    for _ in 0..8 {
+        naive.step();
+        hashlife.step();
+        assert!(naive.get_grid() == hashlife.get_grid());
+    }
+}
+
+#[test]
+fn hashlife_random() {
+    let mut rng = StdRng::seed_from_u64(72114);
+    for _ in 0..200 {
+        let rows = rng.gen_range(0..20);
+        let cols = rng.gen_range(0..20);
+        let grid = random_grid(&mut rng, rows, cols);
+        let mut naive = GameOfLife::from_grid(grid.clone());
+        let mut hashlife = GameOfLife::with_backend(grid, Backend::Hashlife);
+        for _ in 0..4 {
+            let k = rng.gen_range(0..6);
+            for _ in 0..1 << k {
+                naive.step();
+            }
+            hashlife.step_pow2(k);
+            assert!(naive.get_grid() == hashlife.get_grid());
+        }
+    }
+}
+
+#[test]
+fn hashlife_step_pow2() {
+    let mut rng = StdRng::seed_from_u64(5590);
+    let grid = random_grid(&mut rng, 48, 40);
+    let mut naive = GameOfLife::from_grid(grid.clone());
+    let mut hashlife = GameOfLife::with_backend(grid, Backend::Hashlife);
+    naive.step_pow2(8);
+    hashlife.step_pow2(8);
+    assert!(naive == hashlife);
+}
+
+#[test]
+fn hashlife_long_run() {
+    let mut grid = Grid::new(1000, 1000);
+    // Blinker, which has period 2, and block, which is still.
+    for (row, col) in [
+        (500, 499),
+        (500, 500),
+        (500, 501),
+        (10, 10),
+        (10, 11),
+        (11, 10),
+        (11, 11),
+    ] {
+        grid.set(Cell::Alive, row, col);
+    }
+    let mut game = GameOfLife::with_backend(grid.clone(), Backend::Hashlife);
+    game.step_pow2(60);
+    assert!(game.get_grid() == &grid);
+    game.step();
+    assert!(game.get_grid() != &grid);
+    assert!(game.get_grid().get(499, 500) == &Cell::Alive);
+}
+
+#[test]
+fn universe_glider() {
+    #[rustfmt::skip]
+    let glider = get_grid(vec![
+        vec![0, 1, 0],
+        vec![0, 0, 1],
+        vec![1, 1, 1],
+    ]);
+    let mut universe = Universe::from_grid(&glider, 0, 0);
+    assert_eq!(
+        universe.bounding_box(),
+        Some(Bounds {
+            top: 0,
+            left: 0,
+            bottom: 2,
+            right: 2
+        })
+    );
+    // The glider moves by one cell down and right every four generations,
+    // far beyond any fixed grid.
+    for _ in 0..4000 {
+        universe.step();
+    }
+    assert_eq!(universe.population(), 5);
+    assert_eq!(
+        universe.bounding_box(),
+        Some(Bounds {
+            top: 1000,
+            left: 1000,
+            bottom: 1002,
+            right: 1002
+        })
+    );
+    assert!(universe.to_grid(1000, 1000, 3, 3) == glider);
+    assert!(universe.to_grid(0, 0, 3, 3) == Grid::new(3, 3));
+    assert!(universe.get(1002, 1000) == Cell::Alive);
+    assert!(universe.get(1000, 1000) == Cell::Dead);
+}
+
+#[test]
+fn universe_negative_coordinates() {
+    let mut universe = Universe::new();
+    assert_eq!(universe.bounding_box(), None);
+    // Blinker across the origin.
+    for col in -1..=1 {
+        universe.set(Cell::Alive, -5, col);
+    }
+    universe.step();
+    let mut cells = universe.cells().collect::<Vec<_>>();
+    cells.sort();
+    assert_eq!(cells, vec![(-6, 0), (-5, 0), (-4, 0)]);
+
+    let bounds = Bounds {
+        top: -5,
+        left: -3,
+        bottom: 3,
+        right: 3,
+    };
+    let mut cells = universe.cells_in(bounds).collect::<Vec<_>>();
+    cells.sort();
+    assert_eq!(cells, vec![(-5, 0), (-4, 0)]);
+
+    universe.set(Cell::Dead, -5, 0);
+    assert_eq!(universe.population(), 2);
+    assert!(
+        universe.to_grid(-6, -1, 3, 3)
+            == get_grid(vec![vec![0, 1, 0], vec![0, 0, 0], vec![0, 1, 0]])
+    );
+}
+
+#[test]
+fn universe_matches_grid() {
+    let mut rng = StdRng::seed_from_u64(31337);
+    for _ in 0..50 {
+        let pattern = random_grid(&mut rng, 10, 10);
+        // Patterns grow by at most a cell per generation, so the border of the
+        // grid does not matter for the first 10 of them.
+        let mut grid = Grid::new(30, 30);
+        for row in 0..10 {
+            for col in 0..10 {
+                grid.set(*pattern.get(row, col), row + 10, col + 10);
+            }
+        }
+        let mut game = GameOfLife::from_grid(grid.clone());
+        let mut universe = Universe::from_grid(&pattern, -3, 7);
+        assert!(universe.to_grid(-13, -3, 30, 30) == grid);
+        for _ in 0..10 {
+            game.step();
+            universe.step();
+            assert!(universe.to_grid(-13, -3, 30, 30) == *game.get_grid());
+        }
+    }
+}
+
+#[test]
+fn grid_neighbours_topologies() {
+    let neighbours = |topology| {
+        Grid::<i32>::new(3, 4)
+            .with_topology(topology)
+            .neighbours(0, 0)
+    };
+    assert_eq!(neighbours(Topology::Dead), vec![(0, 1), (1, 0), (1, 1)]);
+    assert_eq!(
+        neighbours(Topology::Torus),
+        vec![
+            (2, 3),
+            (2, 0),
+            (2, 1),
+            (0, 3),
+            (0, 1),
+            (1, 3),
+            (1, 0),
+            (1, 1)
+        ]
+    );
+    assert_eq!(
+        neighbours(Topology::KleinBottle),
+        vec![
+            (2, 0),
+            (2, 3),
+            (2, 2),
+            (0, 3),
+            (0, 1),
+            (1, 3),
+            (1, 0),
+            (1, 1)
+        ]
+    );
+    assert_eq!(
+        neighbours(Topology::Mirror),
+        vec![
+            (0, 0),
+            (0, 0),
+            (0, 1),
+            (0, 0),
+            (0, 1),
+            (1, 0),
+            (1, 0),
+            (1, 1)
+        ]
+    );
+    assert_eq!(
+        Grid::<i32>::new(3, 4)
+            .with_topology(Topology::KleinBottle)
+            .neighbours(2, 3),
+        vec![
+            (1, 2),
+            (1, 3),
+            (1, 0),
+            (2, 2),
+            (2, 0),
+            (0, 1),
+            (0, 0),
+            (0, 3)
+        ]
+    );
+}
+
+/// Returns a grid of `rows` × `cols` cells, where the cell at (row, col) is
+/// the cell of `grid` at `cell(row, col)`.
+fn map_grid(
+    grid: &Grid<Cell>,
+    rows: usize,
+    cols: usize,
+    cell: impl Fn(usize, usize) -> (usize, usize),
+) -> Grid<Cell> {
+    let mut result = Grid::new(rows, cols);
+    for row in 0..rows {
+        for col in 0..cols {
+            let (r, c) = cell(row, col);
+            result.set(*grid.get(r, c), row, col);
+        }
+    }
+    result
+}
+
+#[test]
+fn torus_glider() {
+    #[rustfmt::skip]
+    let glider = get_grid(vec![
+        vec![0, 1, 0],
+        vec![0, 0, 1],
+        vec![1, 1, 1],
+    ]);
+    let mut grid = Grid::new(8, 6).with_topology(Topology::Torus);
+    for row in 0..3 {
+        for col in 0..3 {
+            grid.set(*glider.get(row, col), row, col);
+        }
+    }
+    let mut game = GameOfLife::from_grid(grid.clone());
+    // The glider moves by a cell diagonally every four generations, and returns
+    // after passing every row 3 times and every column 4 times.
+    game.step_pow2(2);
+    assert!(game.get_grid() != &grid);
+    for _ in 0..23 {
+        game.step_pow2(2);
+    }
+    assert!(game.get_grid() == &grid);
+}
+
+#[test]
+fn torus_matches_tiling() {
+    let mut rng = StdRng::seed_from_u64(804);
+    for _ in 0..30 {
+        let (rows, cols) = (rng.gen_range(4..10), rng.gen_range(4..10));
+        let grid = random_grid(&mut rng, rows, cols);
+        // Cells of the middle tile do not feel the outer edges for a while.
+        let tiled = map_grid(&grid, 3 * rows, 3 * cols, |row, col| {
+            (row % rows, col % cols)
+        });
+        let mut torus = GameOfLife::from_grid(grid.with_topology(Topology::Torus));
+        let mut tiled = GameOfLife::from_grid(tiled);
+        for _ in 0..rows.min(cols) {
+            torus.step();
+            tiled.step();
+            let middle = map_grid(tiled.get_grid(), rows, cols, |row, col| {
+                (row + rows, col + cols)
+            });
+            assert!(middle.with_topology(Topology::Torus) == *torus.get_grid());
+        }
+    }
+}
+
+#[test]
+fn klein_bottle_and_mirror_match_torus() {
+    let mut rng = StdRng::seed_from_u64(2718);
+    for _ in 0..30 {
+        let (rows, cols) = (rng.gen_range(3..10), rng.gen_range(3..10));
+        let grid = random_grid(&mut rng, rows, cols);
+        // A Klein bottle is a torus of twice the height, whose lower half is
+        // the upper one flipped. Mirrored edges make a torus of twice the size
+        // of four mirror images.
+        let klein_cover = map_grid(&grid, 2 * rows, cols, |row, col| {
+            if row < rows {
+                (row, col)
+            } else {
+                (row - rows, cols - 1 - col)
+            }
+        });
+        let reflect = |value: usize, size: usize| {
+            if value < size {
+                value
+            } else {
+                2 * size - 1 - value
+            }
+        };
+        let mirror_cover = map_grid(&grid, 2 * rows, 2 * cols, |row, col| {
+            (reflect(row, rows), reflect(col, cols))
+        });
+
+        let mut klein = GameOfLife::from_grid(grid.clone().with_topology(Topology::KleinBottle));
+        let mut mirror = GameOfLife::from_grid(grid.with_topology(Topology::Mirror));
+        let mut klein_cover = GameOfLife::from_grid(klein_cover.with_topology(Topology::Torus));
+        let mut mirror_cover = GameOfLife::from_grid(mirror_cover.with_topology(Topology::Torus));
+        for _ in 0..10 {
+            for game in [&mut klein, &mut mirror, &mut klein_cover, &mut mirror_cover] {
+                game.step();
+            }
+            let expected = map_grid(klein_cover.get_grid(), rows, cols, |row, col| (row, col));
+            assert!(expected.with_topology(Topology::KleinBottle) == *klein.get_grid());
+            let expected = map_grid(mirror_cover.get_grid(), rows, cols, |row, col| (row, col));
+            assert!(expected.with_topology(Topology::Mirror) == *mirror.get_grid());
+        }
+    }
+}
+
+#[test]
+#[should_panic]
+fn step_pow2_overflow() {
+    GameOfLife::with_backend(Grid::new(4, 4), Backend::Hashlife).step_pow2(64);
+}
+
+#[test]
+#[should_panic]
+fn hashlife_torus() {
+    let grid = Grid::new(4, 4).with_topology(Topology::Torus);
+    GameOfLife::with_backend(grid, Backend::Hashlife);
+}
@@ -0,0 +1,211 @@
+use std::io::{self, Write};
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    normalize::{Keyed, Normalizer},
+    output::{Output, Position, Sort},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Output column of a line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Column {
+    /// Lines that occur only in the first file.
+    First,
+    /// Lines that occur only in the second file.
+    Second,
+    /// Lines that occur in both files.
+    Common,
+}
+
+/// Columns selected for output. Like in POSIX `comm`, every line is indented
+/// by one tab per selected column preceding its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Columns {
+    pub first: bool,
+    pub second: bool,
+    pub common: bool,
+}
+
+impl Columns {
+    /// All three columns, the default of POSIX `comm`.
+    pub const ALL: Columns = Columns {
+        first: true,
+        second: true,
+        common: true,
+    };
+
+    /// Only common lines without indentation, same as `comm -12`.
+    pub const COMMON: Columns = Columns {
+        first: false,
+        second: false,
+        common: true,
+    };
+
+    /// Returns the indentation of `column`, or `None` if it is not selected.
+    pub fn indent(self, column: Column) -> Option<usize> {
+        let (selected, preceding) = match column {
+            Column::First => (self.first, 0),
+            Column::Second => (self.second, self.first as usize),
+            Column::Common => (self.common, self.first as usize + self.second as usize),
+        };
+        selected.then_some(preceding)
+    }
+}
+
+/// File whose occurrence of a common line is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Record {
+    First,
+    Second,
+}
+
+impl Record {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "file1" => Some(Record::First),
+            "file2" => Some(Record::Second),
+            _ => None,
+        }
+    }
+}
+
+/// Options of a comparison.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Options {
+    pub columns: Columns,
+    /// If false, every distinct line is printed at most once. If true, a line
+    /// occurring `a` times in the first file and `b` times in the second one is
+    /// printed `min(a, b)` times as common and the rest in its own column.
+    pub multiset: bool,
+    /// Lines are matched by their normalized keys, but written as they are.
+    pub normalizer: Normalizer,
+    /// Which of the matched lines is written as common.
+    pub emit: Record,
+    /// Sort the output instead of writing it in the order of comparison.
+    pub sort: Option<Sort>,
+    /// Write lines in the order of this file instead of the order of comparison.
+    pub order: Option<Record>,
+    /// Byte that terminates input and output lines, `\n` or `\0`.
+    pub delimiter: u8,
+}
+
+impl Options {
+    /// Returns true if distinct lines of the second file are kept in memory
+    /// in addition to lines of the first one.
+    pub fn keeps_second(&self) -> bool {
+        self.columns.second && !self.multiset
+    }
+
+    /// Returns true if lines of the first file have to be kept as they are in
+    /// addition to their keys.
+    fn keeps_first_lines(&self) -> bool {
+        self.columns.first || self.emit == Record::First
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Occurrences of a line of the first file.
+struct Entry {
+    /// Index of the first occurrence in the first file, `usize::MAX` for lines
+    /// that occur only in the second file.
+    order: usize,
+    /// Number of occurrences not matched with the second file yet.
+    count: usize,
+    /// First occurrence in the first file if it differs from its key.
+    original: Option<Vec<u8>>,
+    /// Index of the first occurrence in the second file, if any is read yet.
+    second: Option<usize>,
+}
+
+/// Compares lines of `first` and `second` and writes selected columns to `out`.
+///
+/// Common lines and lines of the second file are written in the order of the
+/// second file, followed by lines of the first file in the order of their first
+/// occurrence. Lines of the first file are written as they first occur in the
+/// first file, and so are common lines unless `options.emit` selects the line of
+/// the second file.
+pub fn compare<I, J, W>(
+    first: I,
+    second: J,
+    options: &Options,
+    out: &mut Output<W>,
+) -> io::Result<()>
+where
+    I: IntoIterator<Item = io::Result<Vec<u8>>>,
+    J: IntoIterator<Item = io::Result<Vec<u8>>>,
+    W: Write,
+{
+    let columns = options.columns;
+    let mut lines = FxHashMap::<Vec<u8>, Entry>::default();
+    for (order, line) in first.into_iter().enumerate() {
+        let (key, original) = Keyed::new(line?, &options.normalizer).into_key();
+        let entry = lines.entry(key).or_insert(Entry {
+            order,
+            count: 0,
+            original: original.filter(|_| options.keeps_first_lines()),
+            second: None,
+        });
+        if options.multiset || entry.count == 0 {
+            entry.count += 1;
+        }
+    }
+
+    let mut unmatched = lines.values().map(|entry| entry.count).sum::<usize>();
+    for (index, line) in second.into_iter().enumerate() {
+        if unmatched == 0 && !columns.second {
+            break;
+        }
+        let line = Keyed::new(line?, &options.normalizer);
+        match lines.get_mut(line.key()) {
+            Some(entry) if entry.count > 0 => {
+                entry.count -= 1;
+                unmatched -= 1;
+                let second = *entry.second.get_or_insert(index);
+                let common = match options.emit {
+                    Record::First => entry.original.as_deref().unwrap_or(line.key()),
+                    Record::Second => &line.line,
+                };
+                out.write(Column::Common, common, Position::both(entry.order, second))?;
+            }
+            // Already printed as common or as a line of the second file.
+            Some(_) if !options.multiset => {}
+            Some(entry) => {
+                let second = *entry.second.get_or_insert(index);
+                out.write(Column::Second, &line.line, Position::second(second))?;
+            }
+            None => {
+                out.write(Column::Second, &line.line, Position::second(index))?;
+                // Repeated lines of the multiset are ordered by the first one.
+                if options.keeps_second() || (columns.second && options.order.is_some()) {
+                    let (key, _) = line.into_key();
+                    let entry = Entry {
+                        order: usize::MAX,
+                        count: 0,
+                        original: None,
+                        second: Some(index),
+                    };
+                    lines.insert(key, entry);
+                }
+            }
+        }
+    }
+
+    if columns.first && unmatched > 0 {
+        let mut rest = lines
+            .into_iter()
+            .filter(|(_, entry)| entry.count > 0)
+            .collect::<Vec<_>>();
+        rest.sort_unstable_by_key(|(_, entry)| entry.order);
+        for (key, entry) in rest {
+            let original = entry.original.unwrap_or(key);
+            for _ in 0..entry.count {
+                out.write(Column::First, &original, Position::first(entry.order))?;
+            }
+        }
+    }
+    Ok(())
+}
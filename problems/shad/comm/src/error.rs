@@ -0,0 +1,84 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Error that ends comm, see `exit_code` for the exit status of each variant.
+///
+/// Comparisons pass errors on as `io::Error`, so errors with a known cause are
+/// wrapped into it by `into_io` and recovered by `from_io`.
+#[derive(Error, Debug)]
+pub enum CommError {
+    #[error("{0}")]
+    Usage(String),
+    #[error("{}: no such file or directory", .0.display())]
+    NotFound(PathBuf),
+    #[error("{}: permission denied", .0.display())]
+    PermissionDenied(PathBuf),
+    #[error("{}: read error: {source}", .path.display())]
+    Read { path: PathBuf, source: io::Error },
+    #[error("{}:{line}: input is not sorted", .path.display())]
+    Unsorted { path: PathBuf, line: usize },
+    #[error("temporary file error: {0}")]
+    TempFile(io::Error),
+    #[error("write error: {0}")]
+    Write(io::Error),
+    /// Stdout was closed by the reader, e.g. by `head`.
+    #[error("broken pipe")]
+    BrokenPipe,
+}
+
+impl CommError {
+    /// Exit codes, also listed in the usage message.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CommError::Usage(_) => 2,
+            CommError::NotFound(_) => 3,
+            CommError::PermissionDenied(_) => 4,
+            CommError::Read { .. } => 5,
+            CommError::Unsorted { .. } => 6,
+            CommError::TempFile(_) => 7,
+            CommError::Write(_) => 8,
+            // Same as the status a shell reports for a process killed by SIGPIPE.
+            CommError::BrokenPipe => 141,
+        }
+    }
+
+    /// Classifies an error of opening or reading the input at `path`.
+    pub fn input(path: &Path, err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::NotFound => CommError::NotFound(path.to_path_buf()),
+            io::ErrorKind::PermissionDenied => CommError::PermissionDenied(path.to_path_buf()),
+            _ => CommError::Read {
+                path: path.to_path_buf(),
+                source: err,
+            },
+        }
+    }
+
+    /// Classifies an error of writing the output.
+    pub fn output(err: io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::BrokenPipe => CommError::BrokenPipe,
+            _ => CommError::Write(err),
+        }
+    }
+
+    pub fn into_io(self) -> io::Error {
+        io::Error::other(self)
+    }
+
+    /// Recovers an error wrapped by `into_io`, other errors are taken for errors
+    /// of temporary files, the only files that are not wrapped.
+    pub fn from_io(err: io::Error) -> Self {
+        if err.get_ref().is_some_and(|inner| inner.is::<CommError>()) {
+            let inner = err.into_inner().expect("checked above");
+            return *inner.downcast().expect("checked above");
+        }
+        CommError::TempFile(err)
+    }
+}
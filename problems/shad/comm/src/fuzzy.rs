@@ -0,0 +1,281 @@
+use std::{
+    cmp::Ordering,
+    hash::BuildHasher,
+    io::{self, Write},
+};
+
+use rustc_hash::{FxBuildHasher, FxHashMap, FxHashSet};
+
+use crate::{
+    compare::{Column, Options},
+    normalize::Keyed,
+    output::{Output, Position},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Similarity of two keys, from 0 for unrelated keys to 1 for equal ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// Size of the intersection of the sets of n-grams of the keys divided by
+    /// the size of their union.
+    Jaccard,
+    /// One minus the Levenshtein distance between the keys in bytes divided
+    /// by the length of the longer key.
+    Edit,
+}
+
+impl Metric {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "jaccard" => Some(Metric::Jaccard),
+            "edit" => Some(Metric::Edit),
+            _ => None,
+        }
+    }
+}
+
+/// Settings of the fuzzy comparison.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FuzzyConfig {
+    pub metric: Metric,
+    /// Lowest similarity of matching keys, greater than 0 and at most 1.
+    pub threshold: f64,
+    /// Length of n-grams in bytes, at least 1.
+    pub ngram: usize,
+}
+
+/// Maximal number of MinHash values in the signature of a key.
+const MAX_SIGNATURE_LEN: usize = 1024;
+
+/// Maximal number of signature values in a band. Unrelated keys almost never
+/// share longer bands anyway, so they would only make signatures longer.
+const MAX_ROWS: usize = 16;
+
+/// Lowest probability that a pair of keys that may match gets compared.
+const RECALL: f64 = 0.99;
+
+impl FuzzyConfig {
+    /// Lowest Jaccard similarity of the n-gram sets of keys that may match.
+    ///
+    /// An edit changes at most `ngram` n-grams of a key, so keys at edit
+    /// similarity `t` have at least `1 - ngram * (1 - t)` of their n-grams in
+    /// common relative to their length, which bounds the Jaccard similarity
+    /// up to the few n-grams at the ends of the keys.
+    fn min_jaccard(&self) -> f64 {
+        match self.metric {
+            Metric::Jaccard => self.threshold,
+            Metric::Edit => {
+                let changed = self.ngram as f64 * (1.0 - self.threshold);
+                ((1.0 - changed) / (1.0 + changed)).max(0.0)
+            }
+        }
+    }
+
+    /// Returns the number of signature values in a band of LSH and the number
+    /// of bands, such that keys at `min_jaccard` share some band with probability
+    /// at least `RECALL`. Bands are as long as the signature length and `MAX_ROWS`
+    /// allow, since longer bands leave fewer false candidates.
+    fn bands(&self) -> (usize, usize) {
+        let similarity = self.min_jaccard();
+        // A band of `rows` values is shared with probability `similarity^rows`.
+        let bands = |rows: i32| match similarity.powi(rows) {
+            0.0 => f64::INFINITY,
+            1.0 => 1.0,
+            shared => ((1.0 - RECALL).ln() / (1.0 - shared).ln()).ceil(),
+        };
+        (1..=MAX_ROWS)
+            .map(|rows| (rows, bands(rows as i32)))
+            .take_while(|&(rows, bands)| rows as f64 * bands <= MAX_SIGNATURE_LEN as f64)
+            .last()
+            .map_or((1, MAX_SIGNATURE_LEN), |(rows, bands)| {
+                (rows, bands as usize)
+            })
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Key of a line with its sorted distinct n-gram hashes.
+struct Sketch {
+    line: Keyed,
+    grams: Vec<u64>,
+}
+
+impl Sketch {
+    fn new(line: Keyed, ngram: usize) -> Self {
+        let key = line.key();
+        // Keys shorter than an n-gram, including empty ones, are a single n-gram.
+        let mut grams = key
+            .windows(ngram.min(key.len()).max(1))
+            .map(|gram| mix(FxBuildHasher.hash_one(gram)))
+            .collect::<Vec<_>>();
+        if key.is_empty() {
+            grams.push(mix(FxBuildHasher.hash_one(key)));
+        }
+        grams.sort_unstable();
+        grams.dedup();
+        Self { line, grams }
+    }
+
+    /// MinHash signature: for each hash function `a * gram + b` given by its
+    /// `coefficients`, the least hash of an n-gram. Two keys have the same value
+    /// of a function with the probability of about the Jaccard similarity of
+    /// their n-grams.
+    fn signature(&self, coefficients: &[(u64, u64)]) -> Vec<u64> {
+        let mut signature = vec![u64::MAX; coefficients.len()];
+        for &gram in &self.grams {
+            for (value, &(a, b)) in signature.iter_mut().zip(coefficients) {
+                *value = (*value).min(a.wrapping_mul(gram).wrapping_add(b));
+            }
+        }
+        signature
+    }
+
+    fn similarity(&self, other: &Sketch, config: &FuzzyConfig) -> f64 {
+        match config.metric {
+            Metric::Jaccard => jaccard(&self.grams, &other.grams),
+            Metric::Edit => edit_similarity(self.line.key(), other.line.key(), config.threshold),
+        }
+    }
+}
+
+/// Finalizer of SplitMix64, turns a hash or a seed into a well distributed one.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Jaccard similarity of two sorted sets.
+fn jaccard(a: &[u64], b: &[u64]) -> f64 {
+    let (mut i, mut j, mut common) = (0, 0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            Ordering::Less => i += 1,
+            Ordering::Greater => j += 1,
+            Ordering::Equal => {
+                common += 1;
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    common as f64 / (a.len() + b.len() - common) as f64
+}
+
+/// Edit similarity of `a` and `b`, or 0 if their lengths alone make it less
+/// than `threshold`.
+fn edit_similarity(a: &[u8], b: &[u8], threshold: f64) -> f64 {
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let similarity = |distance: usize| 1.0 - distance as f64 / longest as f64;
+    if similarity(a.len().abs_diff(b.len())) < threshold {
+        return 0.0;
+    }
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+    for (i, &x) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, &y) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(x != y);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    similarity(previous[b.len()])
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// For every distinct key of `first`, finds the most similar key of `second`
+/// by `config.metric`, and if their similarity is at least `config.threshold`,
+/// writes both lines and the similarity with three decimals separated by tabs
+/// into the common column. Lines are written in the order of `first`, ties are
+/// won by the earliest line of `second`.
+///
+/// Lines of `second` and distinct keys of `first` are kept in memory. Instead
+/// of comparing every pair of keys, keys are bucketed by bands of their MinHash
+/// signatures, and only keys sharing a bucket are compared. Pairs above the
+/// threshold are missed with a probability of at most 1 - `RECALL`; for the
+/// edit metric the bound is approximate, and keys without common n-grams are
+/// never compared.
+pub fn compare<I, J, W>(
+    first: I,
+    second: J,
+    options: &Options,
+    config: &FuzzyConfig,
+    out: &mut Output<W>,
+) -> io::Result<()>
+where
+    I: IntoIterator<Item = io::Result<Vec<u8>>>,
+    J: IntoIterator<Item = io::Result<Vec<u8>>>,
+    W: Write,
+{
+    let (rows, bands) = config.bands();
+    // Odd multipliers keep the hash functions bijective.
+    let coefficients = (0..rows * bands)
+        .map(|seed| (mix(2 * seed as u64) | 1, mix(2 * seed as u64 + 1)))
+        .collect::<Vec<_>>();
+    let band_keys = |sketch: &Sketch| {
+        sketch
+            .signature(&coefficients)
+            .chunks(rows)
+            .enumerate()
+            .map(|(band, values)| (band, FxBuildHasher.hash_one(values)))
+            .collect::<Vec<_>>()
+    };
+
+    let mut sketches = Vec::new();
+    // Indices of the first lines of distinct keys of `second`.
+    let mut indices = Vec::new();
+    let mut seen = FxHashSet::default();
+    let mut buckets = FxHashMap::<(usize, u64), Vec<usize>>::default();
+    for (index, line) in second.into_iter().enumerate() {
+        let line = Keyed::new(line?, &options.normalizer);
+        if !seen.insert(line.key().to_vec()) {
+            continue;
+        }
+        let sketch = Sketch::new(line, config.ngram);
+        for band in band_keys(&sketch) {
+            buckets.entry(band).or_default().push(sketches.len());
+        }
+        sketches.push(sketch);
+        indices.push(index);
+    }
+
+    seen.clear();
+    let mut candidates = Vec::new();
+    for (index, line) in first.into_iter().enumerate() {
+        let line = Keyed::new(line?, &options.normalizer);
+        if !seen.insert(line.key().to_vec()) {
+            continue;
+        }
+        let sketch = Sketch::new(line, config.ngram);
+        candidates.clear();
+        for band in band_keys(&sketch) {
+            candidates.extend(buckets.get(&band).into_iter().flatten());
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+
+        let mut best: Option<(usize, f64)> = None;
+        for &candidate in &candidates {
+            let score = sketch.similarity(&sketches[candidate], config);
+            if score >= config.threshold && best.is_none_or(|(_, best)| score > best) {
+                best = Some((candidate, score));
+            }
+        }
+        if let Some((candidate, score)) = best {
+            let mut pair = sketch.line.line;
+            pair.push(b'\t');
+            pair.extend_from_slice(&sketches[candidate].line.line);
+            pair.extend_from_slice(format!("\t{:.3}", score).as_bytes());
+            let position = Position::both(index, indices[candidate]);
+            out.write(Column::Common, &pair, position)?;
+        }
+    }
+    Ok(())
+}
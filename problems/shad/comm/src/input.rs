@@ -0,0 +1,169 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+use flate2::bufread::MultiGzDecoder;
+
+use crate::error::CommError;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Iterator over records of a reader separated by a delimiter byte. Records are
+/// returned as raw bytes without the delimiter, so they do not have to be valid
+/// UTF-8. The last record does not have to end with the delimiter.
+pub struct Records<R> {
+    reader: R,
+    delimiter: u8,
+}
+
+impl<R: BufRead> Records<R> {
+    pub fn new(reader: R, delimiter: u8) -> Self {
+        Self { reader, delimiter }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut record = Vec::new();
+        match self.reader.read_until(self.delimiter, &mut record) {
+            Ok(0) => None,
+            Ok(_) => {
+                if record.last() == Some(&self.delimiter) {
+                    record.pop();
+                }
+                Some(Ok(record))
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// Compression format of an input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Longest magic number of a format.
+    const MAGIC_LEN: usize = 4;
+
+    /// Detects the format by the magic number at the start of `data`.
+    fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0x1f, 0x8b]) {
+            Some(Compression::Gzip)
+        } else if data.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Compression::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns true if `path` stands for stdin.
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Input that wraps its errors into `CommError` naming its path.
+pub struct Reader {
+    inner: Box<dyn BufRead>,
+    size: Option<u64>,
+    path: PathBuf,
+}
+
+impl Reader {
+    /// Returns the number of bytes of the input after decompression if it is
+    /// known without reading it, that is for uncompressed regular files.
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    fn error(path: &Path, err: io::Error) -> io::Error {
+        // Interrupted reads are retried by the callers.
+        if err.kind() == io::ErrorKind::Interrupted {
+            return err;
+        }
+        CommError::input(path, err).into_io()
+    }
+}
+
+impl Read for Reader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner
+            .read(buf)
+            .map_err(|err| Reader::error(&self.path, err))
+    }
+}
+
+impl BufRead for Reader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner
+            .fill_buf()
+            .map_err(|err| Reader::error(&self.path, err))
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+/// Opens the file at `path`, or stdin if it is `-`, and decompresses it on the fly
+/// if it starts with the magic number of gzip or zstd.
+///
+/// The input is opened and read only once, so `path` may be a pipe, e.g. one
+/// made by process substitution.
+pub fn reader(path: &Path) -> io::Result<Reader> {
+    let open = || {
+        if is_stdin(path) {
+            return decode(io::stdin().lock(), None);
+        }
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        // Pipes and devices have no length known in advance.
+        let size = metadata.is_file().then_some(metadata.len());
+        decode(BufReader::new(file), size)
+    };
+    let (inner, size) = open().map_err(|err| Reader::error(path, err))?;
+    Ok(Reader {
+        inner,
+        size,
+        path: path.to_path_buf(),
+    })
+}
+
+/// Wraps `reader` into a decoder if it is compressed. `size` of the input is
+/// kept only if it is not.
+fn decode<R: BufRead + 'static>(
+    mut reader: R,
+    size: Option<u64>,
+) -> io::Result<(Box<dyn BufRead>, Option<u64>)> {
+    // A single read may return fewer bytes than the magic number has, so read
+    // them separately and put them back in front of the rest.
+    let mut magic = Vec::with_capacity(Compression::MAGIC_LEN);
+    (&mut reader)
+        .take(Compression::MAGIC_LEN as u64)
+        .read_to_end(&mut magic)?;
+    let compression = Compression::detect(&magic);
+    let reader = io::Cursor::new(magic).chain(reader);
+    let decoded: Box<dyn BufRead> = match compression {
+        Some(Compression::Gzip) => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Some(Compression::Zstd) => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        None => Box::new(reader),
+    };
+    Ok((decoded, size.filter(|_| compression.is_none())))
+}
+
+/// Returns records of the input at `path` separated by `delimiter`, see `reader`.
+pub fn open(path: &Path, delimiter: u8) -> io::Result<Records<Reader>> {
+    Ok(Records::new(reader(path)?, delimiter))
+}
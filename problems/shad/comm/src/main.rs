@@ -1,37 +1,561 @@
 #![forbid(unsafe_code)]
 use std::{
-    collections::HashSet,
     env,
-    fs::File,
-    io::{BufRead, BufReader},
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    process,
 };
 
-fn main() {
-    let args = env::args().collect::<Vec<String>>();
-
-    if args.len() != 3 {
-        eprintln!("Usage args: file1 file2");
-        std::process::exit(1);
-    }
-
-    let mut set = HashSet::new();
-    
-    for i in 1..args.len() {
-        let file = File::open(&args[i]).unwrap();
-        let reader = BufReader::new(file);
-        
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                if i == 1 {
-                    set.insert(line);
-                }
-                else if let Some(_) = set.take(&line) {
-                    println!("{}", line);
-                    if set.is_empty() {
-                        return;
-                    }
+mod compare;
+mod error;
+mod fuzzy;
+mod input;
+mod merge;
+mod normalize;
+mod output;
+mod parallel;
+mod setops;
+mod spill;
+mod stats;
+
+use compare::{Columns, Options, Record};
+use error::CommError;
+use fuzzy::{FuzzyConfig, Metric};
+use merge::Collation;
+use normalize::{Form, KeyFields, Normalizer};
+use output::{Output, Sort};
+use setops::SetOp;
+use spill::SpillConfig;
+use stats::Format;
+
+const USAGE: &str = "\
+Usage: comm [OPTIONS] [-123] [--columns] [--multiset] [--memory-limit SIZE] file1 file2
+       comm [OPTIONS] [-123] [--columns] [--multiset] --sorted [--collation ORDER] file1 file2
+       comm [OPTIONS] [--op OP | --at-least K] file1 file2 [file...]
+       comm [OPTIONS] --stats [--format FORMAT] file1 file2
+       comm [OPTIONS] --fuzzy METRIC [--threshold T] [--ngram N] file1 file2
+
+Prints every unique line that occurs in both files.
+
+With --columns, prints three columns like POSIX comm: lines only in file1, lines only
+in file2 and lines in both files, each column indented by one more tab. Without it,
+only the third column is printed, same as with -12.
+
+With --op or --at-least, or with more than two files, prints every unique line
+that belongs to the result of the set operation in the order of first occurrence.
+
+With --stats, prints counts instead of lines: for each file the number of lines,
+distinct lines, duplicates (lines that repeat an earlier one) and distinct lines
+absent from the other file; then the number of distinct lines in both files, in
+either file, and the Jaccard similarity, which is their ratio (1 for empty files).
+
+With --fuzzy, lines match if the similarity of their keys is at least the threshold.
+For every distinct line of file1, prints it, its most similar line of file2 and
+their similarity with three decimals, separated by tabs. To avoid comparing every
+pair of lines, only lines with similar MinHash signatures of their n-grams are
+compared, so a matching pair is missed with a probability of about 1%, and lines
+without common n-grams never match. Keeps file2 in memory.
+
+Lines can be matched by normalized keys, which are built by the enabled options
+below in the order they are listed. Matched lines are printed as they first occur
+in the earliest file, lines of the second column as they occur in file2.
+
+With --key, lines are records of fields and are matched only by the listed key
+fields, joined by the separator if there are several, like in join. The whole
+record is printed, for common lines the one from the file selected by --emit.
+
+By default, lines are printed in the order they are found in, which depends on
+the mode: in the order of file2 followed by the rest of file1, in sorted order with
+--sorted, partition by partition with --memory-limit, or in the order of the files
+with --op. --sort and --order print them in an order that depends only on the
+contents of the files: sorted, or in the order of one file followed by the rest
+of the other one. Equal lines are ordered by column, and with --order also by
+their position in the other file. Both keep the whole output in memory.
+
+Lines are compared and printed as raw bytes and do not have to be valid UTF-8.
+Only the terminator is removed, so a carriage return before it is part of the
+line: files with CRLF line endings do not match files with LF ones unless
+--strip-cr is given.
+
+A file can be '-' to read stdin. Files and stdin compressed with gzip or zstd are
+detected by their first bytes and decompressed on the fly. With --memory-limit,
+stdin, pipes and compressed files are always partitioned, since their size is
+unknown.
+
+Options:
+    -z              lines are terminated by NUL instead of newline, both in input
+                    and in output
+    --strip-cr      ignore a trailing carriage return
+    --key           compare only fields LIST, comma-separated field numbers counting
+                    from 1, e.g. '2' or '1,3'; missing fields are empty
+    --field-separator
+                    byte that separates fields of --key (default: tab)
+    --emit          print common lines as they occur in 'file1' (default) or 'file2'
+    --sort          sort printed lines: 'lexicographic' by bytes or 'numeric' by the
+                    leading number, like sort -n
+    --order         print lines in the order of their first occurrence in 'file1' or
+                    'file2', followed by lines that are not in it
+    --ignore-case   compare lines case-insensitively using Unicode case folding
+    --normalize     compare lines in Unicode normalization form FORM: 'nfc' or 'nfkc'
+    --collapse-whitespace
+                    treat every run of whitespace as a single space
+    --trim          ignore leading and trailing whitespace
+    -1              suppress lines that occur only in file1, implies --columns
+    -2              suppress lines that occur only in file2, implies --columns
+    -3              suppress lines that occur in both files, implies --columns
+    --columns       print all columns that are not suppressed
+    --multiset      print a line that occurs N times in one file and M times in the
+                    other min(N, M) times as common and the rest in its own column
+    --threads       number of threads that hash lines when only common lines are
+                    printed (default: all available threads for large inputs)
+    --memory-limit  partition inputs into temporary files so that the lines kept in
+                    memory take about SIZE bytes; accepts K, M and G suffixes
+    --temp-dir      directory for temporary files (default: system temp directory)
+    --sorted        merge inputs that are already sorted in constant memory, failing
+                    on the first line out of order
+    --collation     order of sorted inputs: 'bytes' (default) or 'fold' for
+                    case-insensitive order that does not depend on the locale
+    --op            set operation on all files: 'intersection' (default), 'union',
+                    'difference' (lines of file1 absent from other files) or
+                    'symmetric-difference' (lines present in an odd number of files)
+    --at-least      print lines present in at least K files
+    --stats         print counts of lines instead of the lines
+    --format        format of --stats: 'text' (default) or 'json'
+    --fuzzy         match similar lines by METRIC: 'jaccard' similarity of the sets
+                    of n-grams or 'edit' similarity, which is one minus the
+                    Levenshtein distance divided by the length of the longer line
+    --threshold     lowest similarity of --fuzzy matches, in (0, 1] (default: 0.8)
+    --ngram         length of n-grams of --fuzzy in bytes (default: 3)
+    --help          print this message and exit
+
+Errors are reported by a single line naming the offending file, if any.
+
+Exit status:
+    0               success
+    2               invalid arguments
+    3               a file does not exist
+    4               a file cannot be read due to its permissions
+    5               a file failed to be read or decompressed
+    6               a file is not sorted with --sorted
+    7               a temporary file failed to be written or read
+    8               the output failed to be written
+    141             the output was closed by its reader, nothing is reported";
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// How lines of the files are matched.
+enum Mode {
+    /// Lines of the first file are kept in a hash table. If only common lines
+    /// are printed, the table is split between this many threads, or between all
+    /// available threads for large inputs if `None`.
+    Hash(Option<usize>),
+    /// Inputs are partitioned on disk if they do not fit into memory.
+    Spill(SpillConfig),
+    /// Sorted inputs are merged line by line.
+    Sorted(Collation),
+    /// Set operation on any number of files.
+    Algebra(SetOp),
+    /// Counts of lines of two files.
+    Stats(Format),
+    /// Lines are paired with their most similar lines.
+    Fuzzy(FuzzyConfig),
+}
+
+struct Args {
+    files: Vec<PathBuf>,
+    options: Options,
+    mode: Mode,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = env::args().skip(1);
+    let mut files = Vec::new();
+    let mut memory_limit = None;
+    let mut temp_dir = None;
+    let mut columns = None;
+    let mut multiset = false;
+    let mut sorted = false;
+    let mut collation = None;
+    let mut op = None;
+    let mut normalizer = Normalizer::default();
+    let mut delimiter = b'\n';
+    let mut key = None;
+    let mut separator = None;
+    let mut emit = None;
+    let mut threads = None;
+    let mut sort = None;
+    let mut order = None;
+    let mut stats = false;
+    let mut format = None;
+    let mut metric = None;
+    let mut threshold = None;
+    let mut ngram = None;
+
+    while let Some(arg) = args.next() {
+        if !arg.starts_with('-') || arg == "-" {
+            files.push(PathBuf::from(arg));
+            continue;
+        }
+        if !arg.starts_with("--") {
+            for flag in arg.chars().skip(1) {
+                match flag {
+                    '1' => columns.get_or_insert(Columns::ALL).first = false,
+                    '2' => columns.get_or_insert(Columns::ALL).second = false,
+                    '3' => columns.get_or_insert(Columns::ALL).common = false,
+                    'z' => delimiter = b'\0',
+                    _ => return Err(format!("unknown option -{}", flag)),
                 }
             }
+            continue;
+        }
+        match arg.as_str() {
+            "--columns" => {
+                columns.get_or_insert(Columns::ALL);
+                continue;
+            }
+            "--multiset" => {
+                multiset = true;
+                continue;
+            }
+            "--sorted" => {
+                sorted = true;
+                continue;
+            }
+            "--stats" => {
+                stats = true;
+                continue;
+            }
+            "--strip-cr" => {
+                normalizer.strip_cr = true;
+                continue;
+            }
+            "--ignore-case" => {
+                normalizer.casefold = true;
+                continue;
+            }
+            "--collapse-whitespace" => {
+                normalizer.collapse_whitespace = true;
+                continue;
+            }
+            "--trim" => {
+                normalizer.trim = true;
+                continue;
+            }
+            "--help" => {
+                // Like the rest of the output, a closed stdout is not reported.
+                let _ = writeln!(io::stdout(), "{}", USAGE);
+                process::exit(0);
+            }
+            _ => {}
+        }
+
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for {}", arg))?;
+                (arg, value)
+            }
+        };
+        match name.as_str() {
+            "--memory-limit" => memory_limit = Some(parse_size(&value)?),
+            "--temp-dir" => temp_dir = Some(PathBuf::from(value)),
+            "--collation" => {
+                collation = Some(
+                    Collation::from_name(&value)
+                        .ok_or_else(|| format!("unknown collation '{}'", value))?,
+                )
+            }
+            "--key" => {
+                key = Some(
+                    KeyFields::parse_indices(&value)
+                        .ok_or_else(|| format!("invalid field list '{}'", value))?,
+                )
+            }
+            "--field-separator" => match value.as_bytes() {
+                &[byte] => separator = Some(byte),
+                _ => return Err(format!("field separator must be one byte: '{}'", value)),
+            },
+            "--emit" => {
+                emit = Some(
+                    Record::from_name(&value)
+                        .ok_or_else(|| format!("unknown file '{}' for --emit", value))?,
+                )
+            }
+            "--format" => {
+                format = Some(
+                    Format::from_name(&value)
+                        .ok_or_else(|| format!("unknown format '{}'", value))?,
+                )
+            }
+            "--sort" => {
+                sort = Some(
+                    Sort::from_name(&value)
+                        .ok_or_else(|| format!("unknown sort order '{}'", value))?,
+                )
+            }
+            "--order" => {
+                order = Some(
+                    Record::from_name(&value)
+                        .ok_or_else(|| format!("unknown file '{}' for --order", value))?,
+                )
+            }
+            "--threads" => {
+                threads = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|&threads| threads > 0)
+                        .ok_or_else(|| format!("invalid number of threads '{}'", value))?,
+                )
+            }
+            "--normalize" => {
+                normalizer.form = Some(
+                    Form::from_name(&value)
+                        .ok_or_else(|| format!("unknown normalization form '{}'", value))?,
+                )
+            }
+            "--fuzzy" => {
+                metric = Some(
+                    Metric::from_name(&value)
+                        .ok_or_else(|| format!("unknown metric '{}'", value))?,
+                )
+            }
+            "--threshold" => {
+                threshold = Some(
+                    value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|&threshold| threshold > 0.0 && threshold <= 1.0)
+                        .ok_or_else(|| format!("invalid threshold '{}'", value))?,
+                )
+            }
+            "--ngram" => {
+                ngram = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|&ngram| ngram > 0)
+                        .ok_or_else(|| format!("invalid n-gram length '{}'", value))?,
+                )
+            }
+            "--op" | "--at-least" if op.is_some() => {
+                return Err("only one of --op and --at-least can be given".to_string())
+            }
+            "--op" => {
+                op = Some(
+                    SetOp::from_name(&value)
+                        .ok_or_else(|| format!("unknown operation '{}'", value))?,
+                )
+            }
+            "--at-least" => {
+                op = Some(SetOp::AtLeast(value.parse().map_err(|_| {
+                    format!("invalid value for --at-least: '{}'", value)
+                })?))
+            }
+            _ => return Err(format!("unknown option {}", name)),
+        }
+    }
+
+    if files.len() < 2 {
+        return Err("expected at least two files".to_string());
+    }
+    if files.iter().filter(|path| input::is_stdin(path)).count() > 1 {
+        return Err("stdin can be given as '-' only once".to_string());
+    }
+    if op.is_some() || files.len() > 2 {
+        if columns.is_some() || multiset || sorted || memory_limit.is_some() || emit.is_some() {
+            return Err(
+                "-123, --columns, --multiset, --sorted, --memory-limit and --emit \
+                 take exactly two files and no --op"
+                    .to_string(),
+            );
+        }
+        if let Some(SetOp::AtLeast(count)) = op {
+            if count == 0 || count > files.len() {
+                return Err(format!("--at-least must be between 1 and {}", files.len()));
+            }
+        }
+    }
+    if separator.is_some() && key.is_none() {
+        return Err("--field-separator requires --key".to_string());
+    }
+    normalizer.fields = key.map(|indices| KeyFields {
+        separator: separator.unwrap_or(b'\t'),
+        indices,
+    });
+    if temp_dir.is_some() && memory_limit.is_none() {
+        return Err("--temp-dir requires --memory-limit".to_string());
+    }
+    if collation.is_some() && !sorted {
+        return Err("--collation requires --sorted".to_string());
+    }
+    if sorted && memory_limit.is_some() {
+        return Err("--sorted cannot be combined with --memory-limit".to_string());
+    }
+    if sort.is_some() && order.is_some() {
+        return Err("only one of --sort and --order can be given".to_string());
+    }
+    if (sort.is_some() || order.is_some()) && memory_limit.is_some() {
+        return Err("--sort and --order cannot be combined with --memory-limit".to_string());
+    }
+    if format.is_some() && !stats {
+        return Err("--format requires --stats".to_string());
+    }
+    if stats
+        && (columns.is_some()
+            || multiset
+            || sorted
+            || memory_limit.is_some()
+            || op.is_some()
+            || files.len() > 2
+            || emit.is_some()
+            || sort.is_some()
+            || order.is_some()
+            || threads.is_some())
+    {
+        return Err("--stats takes exactly two files and only options that build keys".to_string());
+    }
+    if threads.is_some() && (sorted || memory_limit.is_some() || op.is_some() || files.len() > 2) {
+        return Err(
+            "--threads cannot be combined with --sorted, --memory-limit or --op".to_string(),
+        );
+    }
+
+    if (threshold.is_some() || ngram.is_some()) && metric.is_none() {
+        return Err("--threshold and --ngram require --fuzzy".to_string());
+    }
+    if metric.is_some()
+        && (columns.is_some()
+            || multiset
+            || sorted
+            || memory_limit.is_some()
+            || op.is_some()
+            || files.len() > 2
+            || emit.is_some()
+            || stats
+            || threads.is_some())
+    {
+        return Err(
+            "--fuzzy takes exactly two files and only options that build keys or order output"
+                .to_string(),
+        );
+    }
+
+    let mode = match (sorted, memory_limit) {
+        _ if metric.is_some() => Mode::Fuzzy(FuzzyConfig {
+            metric: metric.unwrap(),
+            threshold: threshold.unwrap_or(0.8),
+            ngram: ngram.unwrap_or(3),
+        }),
+        _ if stats => Mode::Stats(format.unwrap_or(Format::Text)),
+        _ if op.is_some() || files.len() > 2 => Mode::Algebra(op.unwrap_or(SetOp::Intersection)),
+        (true, _) => Mode::Sorted(collation.unwrap_or(Collation::Bytes)),
+        (false, Some(memory_limit)) => Mode::Spill(SpillConfig {
+            memory_limit,
+            temp_dir,
+        }),
+        (false, None) => Mode::Hash(threads),
+    };
+
+    Ok(Args {
+        files,
+        options: Options {
+            columns: columns.unwrap_or(Columns::COMMON),
+            multiset,
+            normalizer,
+            emit: emit.unwrap_or(Record::First),
+            sort,
+            order,
+            delimiter,
+        },
+        mode,
+    })
+}
+
+/// Parses a number of bytes with an optional binary suffix, e.g. `512K` or `2G`.
+fn parse_size(value: &str) -> Result<u64, String> {
+    let (digits, multiplier) = match value.char_indices().last() {
+        Some((i, 'K' | 'k')) => (&value[..i], 1 << 10),
+        Some((i, 'M' | 'm')) => (&value[..i], 1 << 20),
+        Some((i, 'G' | 'g')) => (&value[..i], 1 << 30),
+        _ => (value, 1),
+    };
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .filter(|&n| n > 0)
+        .ok_or_else(|| format!("invalid size '{}'", value))
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+fn run(args: &Args) -> io::Result<()> {
+    let mut out = Output::new(BufWriter::new(io::stdout().lock()), &args.options);
+    let (first, second) = (&args.files[0], &args.files[1]);
+    let delimiter = args.options.delimiter;
+    match &args.mode {
+        Mode::Spill(config) => spill::compare(first, second, &args.options, config, &mut out),
+        Mode::Sorted(collation) => merge::compare_sorted(
+            (first, input::open(first, delimiter)?),
+            (second, input::open(second, delimiter)?),
+            &args.options,
+            *collation,
+            &mut out,
+        ),
+        Mode::Hash(threads) if parallel::supports(&args.options) => {
+            parallel::compare(first, second, &args.options, *threads, &mut out)
+        }
+        Mode::Hash(_) => compare::compare(
+            input::open(first, delimiter)?,
+            input::open(second, delimiter)?,
+            &args.options,
+            &mut out,
+        ),
+        Mode::Algebra(op) => {
+            let inputs = args
+                .files
+                .iter()
+                .map(|path| input::open(path, delimiter))
+                .collect::<io::Result<Vec<_>>>()?;
+            setops::apply(inputs, *op, &args.options, &mut out)
+        }
+        Mode::Stats(format) => {
+            let stats = stats::count(
+                input::open(first, delimiter)?,
+                input::open(second, delimiter)?,
+                &args.options.normalizer,
+            )?;
+            stats
+                .write(*format, out.get_mut())
+                .map_err(|err| CommError::output(err).into_io())
+        }
+        Mode::Fuzzy(config) => fuzzy::compare(
+            input::open(first, delimiter)?,
+            input::open(second, delimiter)?,
+            &args.options,
+            config,
+            &mut out,
+        ),
+    }?;
+    out.finish()
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        let err = CommError::Usage(err);
+        eprintln!("comm: {} (see 'comm --help')", err);
+        process::exit(err.exit_code());
+    });
+
+    if let Err(err) = run(&args) {
+        let err = CommError::from_io(err);
+        // Like coreutils, exit quietly when the reader of the output goes away.
+        if !matches!(err, CommError::BrokenPipe) {
+            eprintln!("comm: {}", err);
         }
+        process::exit(err.exit_code());
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,181 @@
+use std::{
+    cmp::Ordering,
+    io::{self, Write},
+    path::Path,
+};
+
+use crate::{
+    compare::{Column, Options, Record},
+    error::CommError,
+    normalize::{Keyed, Normalizer},
+    output::{Output, Position},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Order of lines in sorted inputs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collation {
+    /// Byte order, same as `LC_ALL=C sort`.
+    Bytes,
+    /// Lines are compared case-insensitively using Unicode lowercase mapping, lines
+    /// that differ only in case are ordered by bytes. Unlike locale-aware collation
+    /// of `sort`, the order is the same for every locale. Bytes that are not valid
+    /// UTF-8 go after all characters.
+    Fold,
+}
+
+impl Collation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bytes" => Some(Collation::Bytes),
+            "fold" => Some(Collation::Fold),
+            _ => None,
+        }
+    }
+
+    /// Compares two lines. Returns `Ordering::Equal` only for identical lines.
+    pub fn compare(self, a: &[u8], b: &[u8]) -> Ordering {
+        match self {
+            Collation::Bytes => a.cmp(b),
+            Collation::Fold => fold(a).cmp(fold(b)).then_with(|| a.cmp(b)),
+        }
+    }
+}
+
+/// Maps characters of `line` to their lowercase code points and invalid bytes
+/// to numbers after all code points.
+fn fold(line: &[u8]) -> impl Iterator<Item = u32> + '_ {
+    line.utf8_chunks().flat_map(|chunk| {
+        let valid = chunk.valid().chars().flat_map(char::to_lowercase);
+        let invalid = chunk.invalid().iter().map(|&byte| 0x110000 + byte as u32);
+        valid.map(u32::from).chain(invalid)
+    })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Lines of a single input sorted by their keys.
+struct Input<'a, I> {
+    path: &'a Path,
+    lines: I,
+    /// Number of lines read so far.
+    number: usize,
+    /// Index of the first line with the key of the current line.
+    start: usize,
+    /// Key of the previous line.
+    previous: Option<Vec<u8>>,
+    collation: Collation,
+    normalizer: &'a Normalizer,
+    multiset: bool,
+}
+
+impl<'a, I: Iterator<Item = io::Result<Vec<u8>>>> Input<'a, I> {
+    fn new((path, lines): (&'a Path, I), collation: Collation, options: &'a Options) -> Self {
+        Self {
+            path,
+            lines,
+            number: 0,
+            start: 0,
+            previous: None,
+            collation,
+            normalizer: &options.normalizer,
+            multiset: options.multiset,
+        }
+    }
+
+    /// Returns the next line, skipping lines with repeated keys unless in multiset
+    /// mode. Fails if the key goes before the previous one.
+    fn next(&mut self) -> io::Result<Option<Keyed>> {
+        for line in self.lines.by_ref() {
+            self.number += 1;
+            let line = Keyed::new(line?, self.normalizer);
+            let order = match self.previous.as_ref() {
+                Some(previous) => self.collation.compare(previous, line.key()),
+                None => Ordering::Less,
+            };
+            match order {
+                Ordering::Greater => {
+                    let err = CommError::Unsorted {
+                        path: self.path.to_path_buf(),
+                        line: self.number,
+                    };
+                    return Err(err.into_io());
+                }
+                Ordering::Equal if !self.multiset => continue,
+                Ordering::Equal => {}
+                Ordering::Less => self.start = self.number - 1,
+            }
+            self.previous = Some(line.key().to_vec());
+            return Ok(Some(line));
+        }
+        Ok(None)
+    }
+}
+
+/// Same as `compare::compare` for inputs sorted by `collation` of their keys,
+/// but keeps only the current line of each input in memory. Lines are numbered
+/// from 1 and are written in sorted order, like POSIX `comm` does.
+///
+/// Returns an error naming the first line that breaks the order. Lines written
+/// before that are not retracted.
+pub fn compare_sorted<I, J, W>(
+    first: (&Path, I),
+    second: (&Path, J),
+    options: &Options,
+    collation: Collation,
+    out: &mut Output<W>,
+) -> io::Result<()>
+where
+    I: Iterator<Item = io::Result<Vec<u8>>>,
+    J: Iterator<Item = io::Result<Vec<u8>>>,
+    W: Write,
+{
+    let columns = options.columns;
+    let mut first = Input::new(first, collation, options);
+    let mut second = Input::new(second, collation, options);
+
+    let (mut a, mut b) = (first.next()?, second.next()?);
+    loop {
+        let order = match (a.as_ref(), b.as_ref()) {
+            (None, None) => break,
+            (Some(_), None) if !columns.first => break,
+            (None, Some(_)) if !columns.second => break,
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(a), Some(b)) => collation.compare(a.key(), b.key()),
+        };
+        match order {
+            Ordering::Less => {
+                let position = Position::first(first.start);
+                out.write(Column::First, &a.as_ref().unwrap().line, position)?;
+                a = first.next()?;
+            }
+            Ordering::Greater => {
+                let position = Position::second(second.start);
+                out.write(Column::Second, &b.as_ref().unwrap().line, position)?;
+                b = second.next()?;
+            }
+            Ordering::Equal => {
+                let common = match options.emit {
+                    Record::First => a.as_ref().unwrap(),
+                    Record::Second => b.as_ref().unwrap(),
+                };
+                let position = Position::both(first.start, second.start);
+                out.write(Column::Common, &common.line, position)?;
+                a = first.next()?;
+                b = second.next()?;
+            }
+        }
+    }
+
+    // Rest of an input whose column is suppressed is not printed, but still has
+    // to be checked for order.
+    while a.is_some() {
+        a = first.next()?;
+    }
+    while b.is_some() {
+        b = second.next()?;
+    }
+    Ok(())
+}
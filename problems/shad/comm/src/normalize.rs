@@ -0,0 +1,204 @@
+use std::{borrow::Cow, str};
+
+use unicode_normalization::UnicodeNormalization;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Unicode normalization form applied to comparison keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Form {
+    /// Canonical composition, e.g. `e` followed by a combining acute accent
+    /// becomes a precomposed `é`.
+    Nfc,
+    /// Compatibility composition, which also unifies e.g. ligatures and
+    /// full-width characters with their plain counterparts.
+    Nfkc,
+}
+
+impl Form {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "nfc" => Some(Form::Nfc),
+            "nfkc" => Some(Form::Nfkc),
+            _ => None,
+        }
+    }
+}
+
+/// Fields of a record that make up its key, like the join fields of `join`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyFields {
+    /// Byte that separates fields.
+    pub separator: u8,
+    /// Indices of the key fields counting from 1, in the order they are joined.
+    pub indices: Vec<usize>,
+}
+
+impl KeyFields {
+    /// Parses a comma-separated list of field indices, e.g. `1,3`.
+    pub fn parse_indices(list: &str) -> Option<Vec<usize>> {
+        list.split(',')
+            .map(|index| index.parse().ok().filter(|&index| index > 0))
+            .collect()
+    }
+
+    /// Returns the key fields of `line` joined by the separator. Missing fields
+    /// are empty.
+    pub fn extract<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        let field = |index: usize| {
+            line.split(|&byte| byte == self.separator)
+                .nth(index - 1)
+                .unwrap_or_default()
+        };
+        match self.indices.as_slice() {
+            &[index] => Cow::Borrowed(field(index)),
+            indices => {
+                let fields = indices.iter().map(|&index| field(index));
+                Cow::Owned(fields.collect::<Vec<_>>().join(&self.separator))
+            }
+        }
+    }
+}
+
+/// Transformations that turn a line into the key it is compared by.
+/// They are applied in the order of the fields.
+///
+/// Lines that are not valid UTF-8 are normalized byte by byte: only ASCII letters
+/// are case-folded, only ASCII whitespace is collapsed and trimmed, and Unicode
+/// normalization is skipped.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Normalizer {
+    /// Remove a trailing carriage return.
+    pub strip_cr: bool,
+    /// Compare only these fields of the line instead of the whole line.
+    pub fields: Option<KeyFields>,
+    /// Compare case-insensitively. Lines are folded by converting them to
+    /// uppercase and back to lowercase, so that e.g. `ß` matches `SS`.
+    pub casefold: bool,
+    /// Convert to this Unicode normalization form, if any, after case folding.
+    pub form: Option<Form>,
+    /// Replace every run of whitespace with a single space.
+    pub collapse_whitespace: bool,
+    /// Remove leading and trailing whitespace.
+    pub trim: bool,
+}
+
+impl Normalizer {
+    /// Returns the comparison key of `line`.
+    pub fn key<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        if *self == Normalizer::default() {
+            return Cow::Borrowed(line);
+        }
+        let mut line = line;
+        if self.strip_cr {
+            line = line.strip_suffix(b"\r").unwrap_or(line);
+        }
+        match self.fields.as_ref().map(|fields| fields.extract(line)) {
+            Some(Cow::Owned(key)) => Cow::Owned(self.normalize(&key).into_owned()),
+            Some(Cow::Borrowed(key)) => self.normalize(key),
+            None => self.normalize(line),
+        }
+    }
+
+    /// Applies the transformations that follow field extraction.
+    fn normalize<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        match str::from_utf8(line) {
+            Ok(text) => match self.text_key(text) {
+                Cow::Borrowed(key) => Cow::Borrowed(key.as_bytes()),
+                Cow::Owned(key) => Cow::Owned(key.into_bytes()),
+            },
+            Err(_) => self.bytes_key(line),
+        }
+    }
+
+    fn text_key<'a>(&self, line: &'a str) -> Cow<'a, str> {
+        let mut key = Cow::Borrowed(line);
+        if self.casefold {
+            key = Cow::Owned(key.to_uppercase().to_lowercase());
+        }
+        match self.form {
+            Some(Form::Nfc) => key = Cow::Owned(key.nfc().collect()),
+            Some(Form::Nfkc) => key = Cow::Owned(key.nfkc().collect()),
+            None => {}
+        }
+        if self.collapse_whitespace {
+            let mut collapsed = String::with_capacity(key.len());
+            let mut after_space = false;
+            for chr in key.chars() {
+                if !chr.is_whitespace() {
+                    collapsed.push(chr);
+                } else if !after_space {
+                    collapsed.push(' ');
+                }
+                after_space = chr.is_whitespace();
+            }
+            key = Cow::Owned(collapsed);
+        }
+        if self.trim {
+            key = match key {
+                Cow::Borrowed(key) => Cow::Borrowed(key.trim()),
+                Cow::Owned(key) => Cow::Owned(key.trim().to_string()),
+            };
+        }
+        key
+    }
+
+    fn bytes_key<'a>(&self, line: &'a [u8]) -> Cow<'a, [u8]> {
+        let mut key = Cow::Borrowed(line);
+        if self.casefold {
+            key = Cow::Owned(key.to_ascii_lowercase());
+        }
+        if self.collapse_whitespace {
+            let mut collapsed = Vec::with_capacity(key.len());
+            let mut after_space = false;
+            for &byte in key.iter() {
+                if !byte.is_ascii_whitespace() {
+                    collapsed.push(byte);
+                } else if !after_space {
+                    collapsed.push(b' ');
+                }
+                after_space = byte.is_ascii_whitespace();
+            }
+            key = Cow::Owned(collapsed);
+        }
+        if self.trim {
+            key = match key {
+                Cow::Borrowed(key) => Cow::Borrowed(key.trim_ascii()),
+                Cow::Owned(key) => Cow::Owned(key.trim_ascii().to_vec()),
+            };
+        }
+        key
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Line together with its comparison key.
+pub struct Keyed {
+    pub line: Vec<u8>,
+    /// `None` if the key is equal to the line.
+    key: Option<Vec<u8>>,
+}
+
+impl Keyed {
+    pub fn new(line: Vec<u8>, normalizer: &Normalizer) -> Self {
+        let key = match normalizer.key(&line) {
+            Cow::Owned(key) if key != line => Some(key),
+            Cow::Borrowed(key) if key.len() != line.len() => Some(key.to_vec()),
+            _ => None,
+        };
+        Self { line, key }
+    }
+
+    pub fn key(&self) -> &[u8] {
+        self.key.as_deref().unwrap_or(&self.line)
+    }
+
+    /// Splits into the key and the original line if it differs from the key.
+    pub fn into_key(self) -> (Vec<u8>, Option<Vec<u8>>) {
+        match self.key {
+            Some(key) => (key, Some(self.line)),
+            None => (self.line, None),
+        }
+    }
+}
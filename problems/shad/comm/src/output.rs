@@ -0,0 +1,211 @@
+use std::{
+    cmp::Ordering,
+    io::{self, Write},
+};
+
+use crate::{
+    compare::{Column, Options, Record},
+    error::CommError,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Order in which `--sort` prints lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sort {
+    /// Byte order, same as `LC_ALL=C sort`.
+    Lexicographic,
+    /// Order of the number at the start of a line, like `sort -n`. The number
+    /// may follow whitespace and have a minus sign and a fractional part; lines
+    /// without a number count as zero. Lines with equal numbers are ordered by
+    /// bytes.
+    Numeric,
+}
+
+impl Sort {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "lexicographic" => Some(Sort::Lexicographic),
+            "numeric" => Some(Sort::Numeric),
+            _ => None,
+        }
+    }
+
+    fn compare(self, a: &[u8], b: &[u8]) -> Ordering {
+        match self {
+            Sort::Lexicographic => a.cmp(b),
+            Sort::Numeric => compare_numbers(a, b).then_with(|| a.cmp(b)),
+        }
+    }
+}
+
+/// Compares the numbers at the start of `a` and `b` exactly, however long they are.
+fn compare_numbers(a: &[u8], b: &[u8]) -> Ordering {
+    let (a_negative, a_integer, a_fraction) = parse_number(a);
+    let (b_negative, b_integer, b_fraction) = parse_number(b);
+    let magnitude = a_integer
+        .len()
+        .cmp(&b_integer.len())
+        .then_with(|| a_integer.cmp(b_integer))
+        .then_with(|| a_fraction.cmp(b_fraction));
+    match (a_negative, b_negative) {
+        (false, false) => magnitude,
+        (true, true) => magnitude.reverse(),
+        (false, true) => Ordering::Greater,
+        (true, false) => Ordering::Less,
+    }
+}
+
+/// Splits the number at the start of `line` into its sign, integer digits without
+/// leading zeros and fractional digits without trailing zeros. Zero is never negative.
+fn parse_number(line: &[u8]) -> (bool, &[u8], &[u8]) {
+    let digits = |text: &[u8]| text.iter().take_while(|byte| byte.is_ascii_digit()).count();
+    let line = line.trim_ascii_start();
+    let (negative, line) = match line.strip_prefix(b"-") {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (integer, rest) = line.split_at(digits(line));
+    let fraction = match rest.strip_prefix(b".") {
+        Some(rest) => &rest[..digits(rest)],
+        None => &[],
+    };
+    let leading_zeros = integer.iter().take_while(|&&byte| byte == b'0').count();
+    let trailing_zeros = fraction
+        .iter()
+        .rev()
+        .take_while(|&&byte| byte == b'0')
+        .count();
+    let integer = &integer[leading_zeros..];
+    let fraction = &fraction[..fraction.len() - trailing_zeros];
+    let negative = negative && !(integer.is_empty() && fraction.is_empty());
+    (negative, integer, fraction)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Indices of the first occurrences of the key of a written line in the first
+/// two files, counting from 0. Only files the line is written from are set:
+/// both for common lines, one for lines of a single file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    pub first: Option<usize>,
+    pub second: Option<usize>,
+}
+
+impl Position {
+    pub fn first(index: usize) -> Self {
+        Self {
+            first: Some(index),
+            second: None,
+        }
+    }
+
+    pub fn second(index: usize) -> Self {
+        Self {
+            first: None,
+            second: Some(index),
+        }
+    }
+
+    pub fn both(first: usize, second: usize) -> Self {
+        Self {
+            first: Some(first),
+            second: Some(second),
+        }
+    }
+}
+
+/// Line kept until the whole output can be ordered.
+struct Pending {
+    column: Column,
+    line: Vec<u8>,
+    position: Position,
+}
+
+/// Writes lines of selected columns, either at once or, with `options.sort` or
+/// `options.order`, all together in the requested order when finished.
+pub struct Output<'a, W> {
+    out: W,
+    options: &'a Options,
+    pending: Vec<Pending>,
+}
+
+impl<'a, W: Write> Output<'a, W> {
+    pub fn new(out: W, options: &'a Options) -> Self {
+        Self {
+            out,
+            options,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Returns the underlying writer, e.g. to write something other than lines.
+    /// Its errors are not wrapped into `CommError`.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    /// Writes `line` terminated by `options.delimiter` into `column` if it is
+    /// selected.
+    pub fn write(&mut self, column: Column, line: &[u8], position: Position) -> io::Result<()> {
+        if self.options.columns.indent(column).is_none() {
+            return Ok(());
+        }
+        if self.options.sort.is_some() || self.options.order.is_some() {
+            self.pending.push(Pending {
+                column,
+                line: line.to_vec(),
+                position,
+            });
+            return Ok(());
+        }
+        self.write_now(column, line)
+    }
+
+    /// Writes pending lines in order and flushes the output.
+    ///
+    /// With `options.sort`, lines are ordered by it, and identical lines by column
+    /// in the order of `Column`. With `options.order`, lines are ordered by their
+    /// positions in the selected file, lines that are not in it go after all others
+    /// in the order of the other file. Ties are broken by column in the order of
+    /// `Column`, then by the position in the other file. Either way, lines that
+    /// are still equal are identical, so the output depends only on the written
+    /// lines and their positions, not on the order they were written in.
+    pub fn finish(mut self) -> io::Result<()> {
+        let mut pending = std::mem::take(&mut self.pending);
+        match (self.options.sort, self.options.order) {
+            (Some(sort), _) => pending.sort_by(|a, b| {
+                sort.compare(&a.line, &b.line)
+                    .then_with(|| a.column.cmp(&b.column))
+            }),
+            (None, Some(order)) => pending.sort_by_key(|line| {
+                let position = line.position;
+                let (own, other) = match order {
+                    Record::First => (position.first, position.second),
+                    Record::Second => (position.second, position.first),
+                };
+                (own.is_none(), own, line.column, other)
+            }),
+            (None, None) => {}
+        }
+        for line in pending {
+            self.write_now(line.column, &line.line)?;
+        }
+        self.out
+            .flush()
+            .map_err(|err| CommError::output(err).into_io())
+    }
+
+    fn write_now(&mut self, column: Column, line: &[u8]) -> io::Result<()> {
+        if let Some(indent) = self.options.columns.indent(column) {
+            let mut write = || {
+                self.out.write_all(&b"\t\t"[..indent])?;
+                self.out.write_all(line)?;
+                self.out.write_all(&[self.options.delimiter])
+            };
+            write().map_err(|err| CommError::output(err).into_io())?;
+        }
+        Ok(())
+    }
+}
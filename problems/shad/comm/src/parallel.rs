@@ -0,0 +1,211 @@
+use std::{
+    borrow::Cow,
+    hash::BuildHasher,
+    io::{self, Read, Write},
+    num::NonZeroUsize,
+    path::Path,
+    thread,
+};
+
+use rustc_hash::{FxBuildHasher, FxHashMap};
+
+use crate::{
+    compare::{Column, Columns, Options, Record},
+    input,
+    output::{Output, Position},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// The second file is read in blocks of about this many bytes, so that only the
+/// first file is kept in memory as a whole.
+const BLOCK_SIZE: u64 = 16 << 20;
+
+/// Inputs smaller than this in total are hashed on a single thread, since spreading them
+/// over several threads costs more than it saves.
+const PARALLEL_THRESHOLD: u64 = 8 << 20;
+
+/// Returns true if `compare` supports `options`: only common lines are printed,
+/// each distinct line once.
+pub fn supports(options: &Options) -> bool {
+    options.columns == Columns::COMMON && !options.multiset
+}
+
+/// Same as `compare::compare` on the files at `first` and `second` for `options`
+/// that this module `supports`, but faster.
+///
+/// The first file is read into memory at once and its lines are hashed in place,
+/// without copying them. Memory-mapping it instead would take unsafe code.
+///
+/// Lines are hashed on `threads` threads. If it is `None`, large inputs use all
+/// available threads and small ones a single thread. Keys are split between
+/// threads by hash into partitions, and every partition has its own hash table,
+/// so the threads never wait for each other.
+pub fn compare<W: Write>(
+    first: &Path,
+    second: &Path,
+    options: &Options,
+    threads: Option<usize>,
+    out: &mut Output<W>,
+) -> io::Result<()> {
+    let mut data = Vec::new();
+    input::reader(first)?.read_to_end(&mut data)?;
+    let mut reader = input::reader(second)?;
+    let size = data.len() as u64 + reader.size().unwrap_or(0);
+    let threads = match threads {
+        Some(threads) => threads,
+        None if size >= PARALLEL_THRESHOLD => {
+            thread::available_parallelism().map_or(1, NonZeroUsize::get)
+        }
+        None => 1,
+    };
+    let lines = split(&data, options.delimiter);
+    let keys = map_chunks(&lines, threads, |line| Key::new(line, options, threads));
+    let mut tables = partition(&keys, threads)
+        .into_iter()
+        .map(|indices| (indices, FxHashMap::default()))
+        .collect::<Vec<_>>();
+    for_each_partition(&mut tables, |(indices, table)| {
+        for &index in indices.iter() {
+            table.entry(keys[index].key.as_ref()).or_insert(index);
+        }
+    });
+    let mut tables = tables
+        .into_iter()
+        .map(|(_, table)| table)
+        .collect::<Vec<_>>();
+
+    let mut block = Vec::new();
+    // Number of records of the second file in the previous blocks.
+    let mut offset = 0;
+    loop {
+        let read = (&mut reader).take(BLOCK_SIZE).read_to_end(&mut block)?;
+        let end = match memchr::memrchr(options.delimiter, &block) {
+            _ if read == 0 => block.len(),
+            Some(last) => last + 1,
+            None => continue,
+        };
+
+        let records = split(&block[..end], options.delimiter);
+        let second_keys = map_chunks(&records, threads, |line| Key::new(line, options, threads));
+        let mut partitions = partition(&second_keys, threads)
+            .into_iter()
+            .zip(tables.iter_mut())
+            .map(|(indices, table)| (indices, table, Vec::new()))
+            .collect::<Vec<_>>();
+        for_each_partition(&mut partitions, |(indices, table, matches)| {
+            for &index in indices.iter() {
+                if let Some(first_index) = table.remove(second_keys[index].key.as_ref()) {
+                    matches.push((index, first_index));
+                }
+            }
+        });
+
+        let mut matches = partitions
+            .into_iter()
+            .flat_map(|(_, _, matches)| matches)
+            .collect::<Vec<_>>();
+        matches.sort_unstable();
+        for (index, first_index) in matches {
+            let line = match options.emit {
+                Record::First => lines[first_index],
+                Record::Second => records[index],
+            };
+            let position = Position::both(first_index, offset + index);
+            out.write(Column::Common, line, position)?;
+        }
+
+        if read == 0 {
+            return Ok(());
+        }
+        offset += records.len();
+        block.drain(..end);
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Comparison key of a line together with its partition.
+struct Key<'a> {
+    key: Cow<'a, [u8]>,
+    partition: usize,
+}
+
+impl<'a> Key<'a> {
+    fn new(line: &'a [u8], options: &Options, partitions: usize) -> Self {
+        let key = options.normalizer.key(line);
+        // Hash tables index buckets by the lowest bits of the hash, so take
+        // the partition from the upper half to keep buckets evenly used.
+        let hash = FxBuildHasher.hash_one(&key);
+        Self {
+            key,
+            partition: (hash >> 32) as usize % partitions,
+        }
+    }
+}
+
+/// Splits `data` into records terminated by `delimiter`. The last record does
+/// not have to be terminated.
+fn split(data: &[u8], delimiter: u8) -> Vec<&[u8]> {
+    let mut records = Vec::new();
+    let mut start = 0;
+    for end in memchr::memchr_iter(delimiter, data) {
+        records.push(&data[start..end]);
+        start = end + 1;
+    }
+    if start < data.len() {
+        records.push(&data[start..]);
+    }
+    records
+}
+
+/// Returns indices of `keys` in every partition in increasing order.
+fn partition(keys: &[Key], partitions: usize) -> Vec<Vec<usize>> {
+    if partitions == 1 {
+        return vec![(0..keys.len()).collect()];
+    }
+    let mut indices = vec![Vec::new(); partitions];
+    for (index, key) in keys.iter().enumerate() {
+        indices[key.partition].push(index);
+    }
+    indices
+}
+
+/// Maps `items` in chunks on `threads` threads, keeping their order.
+fn map_chunks<T, U, F>(items: &[T], threads: usize, f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> U + Sync,
+{
+    if threads == 1 {
+        return items.iter().map(f).collect();
+    }
+    let chunk = items.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let handles = items
+            .chunks(chunk)
+            .map(|chunk| scope.spawn(|| chunk.iter().map(&f).collect::<Vec<_>>()))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Calls `f` for every partition, each on its own thread if there are several.
+fn for_each_partition<T, F>(partitions: &mut [T], f: F)
+where
+    T: Send,
+    F: Fn(&mut T) + Sync,
+{
+    if let [partition] = partitions {
+        return f(partition);
+    }
+    thread::scope(|scope| {
+        for partition in partitions.iter_mut() {
+            scope.spawn(|| f(partition));
+        }
+    });
+}
@@ -0,0 +1,142 @@
+use std::io::{self, Write};
+
+use rustc_hash::FxHashMap;
+
+use crate::{
+    compare::{Column, Options},
+    normalize::Keyed,
+    output::{Output, Position},
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Operation on the sets of distinct lines of several files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SetOp {
+    /// Lines present in every file.
+    Intersection,
+    /// Lines present in some file.
+    Union,
+    /// Lines of the first file that are absent from all other files.
+    Difference,
+    /// Lines present in an odd number of files, which for two files are
+    /// the lines present in exactly one of them.
+    SymmetricDifference,
+    /// Lines present in at least this many files.
+    AtLeast(usize),
+}
+
+impl SetOp {
+    /// Parses every operation except `AtLeast`, which takes an argument.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "intersection" => Some(SetOp::Intersection),
+            "union" => Some(SetOp::Union),
+            "difference" => Some(SetOp::Difference),
+            "symmetric-difference" => Some(SetOp::SymmetricDifference),
+            _ => None,
+        }
+    }
+
+    /// Returns true if a line first seen in file `index` out of `files` may
+    /// still get into the result.
+    fn may_start(self, index: usize, files: usize) -> bool {
+        match self {
+            SetOp::Intersection | SetOp::Difference => index == 0,
+            SetOp::Union | SetOp::SymmetricDifference => true,
+            SetOp::AtLeast(count) => files - index >= count,
+        }
+    }
+
+    /// Returns true if a line seen in `count` out of `files` files gets into
+    /// the result. Lines of `Difference` are always present in the first file.
+    fn accepts(self, count: usize, files: usize) -> bool {
+        match self {
+            SetOp::Intersection => count == files,
+            SetOp::Union => true,
+            SetOp::Difference => count == 1,
+            SetOp::SymmetricDifference => count % 2 == 1,
+            SetOp::AtLeast(at_least) => count >= at_least,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Files in which a line occurs.
+struct Entry {
+    /// Index of the first occurrence counting lines of all files in order.
+    order: usize,
+    /// Number of files containing the line.
+    count: usize,
+    /// Index of the last file containing the line.
+    last_file: usize,
+    /// First occurrences in the first two files.
+    position: Position,
+    /// First occurrence of the line if it differs from its key.
+    original: Option<Vec<u8>>,
+}
+
+/// Applies `op` to the sets of lines of `inputs`, matched by keys of
+/// `options.normalizer`, and writes the first occurrence of every resulting line
+/// once, in the order of first occurrence in the inputs taken one after another.
+/// Lines are written into the common column, multiset semantics of `options`
+/// are not used.
+pub fn apply<I, W>(
+    inputs: Vec<I>,
+    op: SetOp,
+    options: &Options,
+    out: &mut Output<W>,
+) -> io::Result<()>
+where
+    I: IntoIterator<Item = io::Result<Vec<u8>>>,
+    W: Write,
+{
+    let files = inputs.len();
+    let mut lines = FxHashMap::<Vec<u8>, Entry>::default();
+    let mut order = 0;
+    for (index, input) in inputs.into_iter().enumerate() {
+        let may_start = op.may_start(index, files);
+        for (number, line) in input.into_iter().enumerate() {
+            let line = Keyed::new(line?, &options.normalizer);
+            match lines.get_mut(line.key()) {
+                Some(entry) if entry.last_file != index => {
+                    entry.count += 1;
+                    entry.last_file = index;
+                    if index == 1 {
+                        entry.position.second = Some(number);
+                    }
+                }
+                Some(_) => {}
+                None if may_start => {
+                    let (key, original) = line.into_key();
+                    let entry = Entry {
+                        order,
+                        count: 1,
+                        last_file: index,
+                        position: match index {
+                            0 => Position::first(number),
+                            1 => Position::second(number),
+                            _ => Position::default(),
+                        },
+                        original,
+                    };
+                    lines.insert(key, entry);
+                }
+                None => {}
+            }
+            order += 1;
+        }
+    }
+
+    let mut result = lines
+        .into_iter()
+        .filter(|(_, entry)| op.accepts(entry.count, files))
+        .collect::<Vec<_>>();
+    result.sort_unstable_by_key(|(_, entry)| entry.order);
+    for (key, entry) in result {
+        let line = entry.original.unwrap_or(key);
+        out.write(Column::Common, &line, entry.position)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    compare::{self, Options},
+    input,
+    normalize::Normalizer,
+    output::Output,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Rough ratio between the memory taken by a `HashMap<String, _>` and the total
+/// length of its lines.
+const SET_OVERHEAD: u64 = 4;
+
+/// Maximal number of partitions a file is split into at once.
+const MAX_PARTITIONS: u64 = 64;
+
+/// Partitions are split again at most this many times. Deeper partitions are
+/// intersected in memory regardless of their size, which only happens when
+/// a partition consists of a few very long lines.
+const MAX_LEVEL: u32 = 4;
+
+/// Settings of the external-memory comparison.
+pub struct SpillConfig {
+    /// Approximate number of bytes lines kept by `compare::compare` may take in memory.
+    pub memory_limit: u64,
+    /// Directory for partition files, the system temp directory if `None`.
+    pub temp_dir: Option<PathBuf>,
+}
+
+impl SpillConfig {
+    fn fits(&self, size: u64) -> bool {
+        size.saturating_mul(SET_OVERHEAD) <= self.memory_limit
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Same as `compare::compare` on files at `first` and `second`, but if the lines
+/// it keeps do not fit into `config.memory_limit`, both files are split by line hash
+/// into partitions on disk, and every pair of partitions is compared separately.
+///
+/// Output contains the same lines as the in-memory version, but they are ordered
+/// by partition first and as described in `compare::compare` within a partition.
+pub fn compare<W: Write>(
+    first: &Path,
+    second: &Path,
+    options: &Options,
+    config: &SpillConfig,
+    out: &mut Output<W>,
+) -> io::Result<()> {
+    let first = input::open(first, options.delimiter)?;
+    let second = input::open(second, options.delimiter)?;
+    // Inputs of unknown size, such as stdin, pipes or compressed files, are
+    // assumed not to fit.
+    let mut size = first.get_ref().size().unwrap_or(u64::MAX);
+    if options.keeps_second() {
+        size = size.saturating_add(second.get_ref().size().unwrap_or(u64::MAX));
+    }
+    if config.fits(size) {
+        return compare::compare(first, second, options, out);
+    }
+
+    let builder = tempfile::Builder::new().prefix("comm-").to_owned();
+    let dir = match config.temp_dir.as_ref() {
+        Some(path) => builder.tempdir_in(path)?,
+        None => builder.tempdir()?,
+    };
+    let context = Context {
+        options,
+        config,
+        dir: dir.path(),
+    };
+    split(first, second, size, 0, &context, out)?;
+    dir.close()
+}
+
+/// Parameters shared by all levels of `split`.
+struct Context<'a> {
+    options: &'a Options,
+    config: &'a SpillConfig,
+    dir: &'a Path,
+}
+
+/// Partitions both inputs and compares every pair of partitions, splitting
+/// the ones that still do not fit into memory recursively.
+fn split<I, J, W>(
+    first: I,
+    second: J,
+    size: u64,
+    level: u32,
+    context: &Context,
+    out: &mut Output<W>,
+) -> io::Result<()>
+where
+    I: Iterator<Item = io::Result<Vec<u8>>>,
+    J: Iterator<Item = io::Result<Vec<u8>>>,
+    W: Write,
+{
+    let Context {
+        options,
+        config,
+        dir,
+    } = *context;
+    let count = (size.saturating_mul(SET_OVERHEAD) / config.memory_limit)
+        .saturating_add(1)
+        .clamp(2, MAX_PARTITIONS) as usize;
+    let normalizer = &options.normalizer;
+    let first_prefix = dir.join(format!("{}-a", level));
+    let second_prefix = dir.join(format!("{}-b", level));
+    let first_parts = partition(first, level, count, normalizer, &first_prefix)?;
+    let second_parts = partition(second, level, count, normalizer, &second_prefix)?;
+
+    for ((first, first_size), (second, second_size)) in first_parts.into_iter().zip(second_parts) {
+        let mut size = first_size;
+        if options.keeps_second() {
+            size += second_size;
+        }
+        // Without lines of the first file only the second column can be printed.
+        if first_size > 0 || options.columns.second {
+            let lines = (read_partition(&first)?, read_partition(&second)?);
+            if config.fits(size) || level + 1 == MAX_LEVEL {
+                compare::compare(lines.0, lines.1, options, out)?;
+            } else {
+                split(lines.0, lines.1, size, level + 1, context, out)?;
+            }
+        }
+        fs::remove_file(first)?;
+        fs::remove_file(second)?;
+    }
+    Ok(())
+}
+
+/// Writes every line to one of `count` files named `{prefix}-{index}` chosen by
+/// the hash of its key and `level`. Returns paths of these files and their sizes.
+fn partition<I>(
+    lines: I,
+    level: u32,
+    count: usize,
+    normalizer: &Normalizer,
+    prefix: &Path,
+) -> io::Result<Vec<(PathBuf, u64)>>
+where
+    I: Iterator<Item = io::Result<Vec<u8>>>,
+{
+    let paths = (0..count)
+        .map(|i| PathBuf::from(format!("{}-{}", prefix.display(), i)))
+        .collect::<Vec<_>>();
+    let mut writers = paths
+        .iter()
+        .map(|path| Ok(BufWriter::new(File::create(path)?)))
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut sizes = vec![0; count];
+
+    for line in lines {
+        let line = line?;
+        let mut hasher = DefaultHasher::new();
+        level.hash(&mut hasher);
+        normalizer.key(&line).hash(&mut hasher);
+        let index = (hasher.finish() % count as u64) as usize;
+
+        // Lines may contain any bytes, so they are prefixed with their length.
+        writers[index].write_all(&(line.len() as u64).to_le_bytes())?;
+        writers[index].write_all(&line)?;
+        sizes[index] += line.len() as u64 + 8;
+    }
+    for writer in writers.iter_mut() {
+        writer.flush()?;
+    }
+    Ok(paths.into_iter().zip(sizes).collect())
+}
+
+/// Reads lines written by `partition`.
+fn read_partition(path: &Path) -> io::Result<impl Iterator<Item = io::Result<Vec<u8>>>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    Ok(std::iter::from_fn(move || {
+        let mut len = [0; 8];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return None,
+            Err(err) => return Some(Err(err)),
+        }
+        let mut line = vec![0; u64::from_le_bytes(len) as usize];
+        Some(reader.read_exact(&mut line).map(|()| line))
+    }))
+}
@@ -0,0 +1,147 @@
+use std::io::{self, Write};
+
+use rustc_hash::FxHashMap;
+
+use crate::normalize::Normalizer;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// How `Stats` are printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One `name: value` line per number.
+    Text,
+    /// Single-line JSON object.
+    Json,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Counts of a single file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FileStats {
+    /// Number of lines.
+    pub lines: u64,
+    /// Number of distinct keys.
+    pub distinct: u64,
+    /// Number of distinct keys absent from the other file.
+    pub only: u64,
+}
+
+impl FileStats {
+    /// Number of lines that repeat a key of an earlier line.
+    pub fn duplicates(&self) -> u64 {
+        self.lines - self.distinct
+    }
+}
+
+/// Overlap of the sets of keys of two files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub first: FileStats,
+    pub second: FileStats,
+    /// Number of distinct keys present in both files.
+    pub common: u64,
+}
+
+impl Stats {
+    /// Number of distinct keys present in some file.
+    pub fn union(&self) -> u64 {
+        self.first.distinct + self.second.distinct - self.common
+    }
+
+    /// Size of the intersection divided by the size of the union, 1 if both
+    /// files are empty.
+    pub fn jaccard(&self) -> f64 {
+        match self.union() {
+            0 => 1.0,
+            union => self.common as f64 / union as f64,
+        }
+    }
+
+    pub fn write<W: Write>(&self, format: Format, out: &mut W) -> io::Result<()> {
+        match format {
+            Format::Text => {
+                for (name, file) in [("file1", &self.first), ("file2", &self.second)] {
+                    writeln!(out, "{} lines: {}", name, file.lines)?;
+                    writeln!(out, "{} distinct: {}", name, file.distinct)?;
+                    writeln!(out, "{} duplicates: {}", name, file.duplicates())?;
+                    writeln!(out, "{} only: {}", name, file.only)?;
+                }
+                writeln!(out, "common: {}", self.common)?;
+                writeln!(out, "union: {}", self.union())?;
+                writeln!(out, "jaccard: {}", self.jaccard())
+            }
+            Format::Json => {
+                let file = |file: &FileStats| {
+                    format!(
+                        "{{\"lines\":{},\"distinct\":{},\"duplicates\":{},\"only\":{}}}",
+                        file.lines,
+                        file.distinct,
+                        file.duplicates(),
+                        file.only
+                    )
+                };
+                writeln!(
+                    out,
+                    "{{\"file1\":{},\"file2\":{},\"common\":{},\"union\":{},\"jaccard\":{}}}",
+                    file(&self.first),
+                    file(&self.second),
+                    self.common,
+                    self.union(),
+                    self.jaccard()
+                )
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Counts lines of `first` and `second` matched by keys of `normalizer`. Keys
+/// of both files are kept in memory.
+pub fn count<I, J>(first: I, second: J, normalizer: &Normalizer) -> io::Result<Stats>
+where
+    I: IntoIterator<Item = io::Result<Vec<u8>>>,
+    J: IntoIterator<Item = io::Result<Vec<u8>>>,
+{
+    const FIRST: u8 = 1;
+    const SECOND: u8 = 2;
+
+    let mut stats = Stats::default();
+    let mut keys = FxHashMap::<Vec<u8>, u8>::default();
+    for line in first {
+        stats.first.lines += 1;
+        *keys.entry(normalizer.key(&line?).into_owned()).or_default() |= FIRST;
+    }
+    for line in second {
+        stats.second.lines += 1;
+        let line = line?;
+        let key = normalizer.key(&line);
+        match keys.get_mut(key.as_ref()) {
+            Some(files) => *files |= SECOND,
+            None => {
+                keys.insert(key.into_owned(), SECOND);
+            }
+        }
+    }
+
+    for files in keys.into_values() {
+        match files {
+            FIRST => stats.first.only += 1,
+            SECOND => stats.second.only += 1,
+            _ => stats.common += 1,
+        }
+    }
+    stats.first.distinct = stats.first.only + stats.common;
+    stats.second.distinct = stats.second.only + stats.common;
+    Ok(stats)
+}
@@ -1,9 +1,14 @@
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
+    fs::{self, File},
     io::{self, Write},
-    process::Command,
+    os::unix::fs::PermissionsExt,
+    path::Path,
+    process::{Command, Output, Stdio},
+    thread,
 };
 
+use flate2::{write::GzEncoder, Compression};
 use pretty_assertions::assert_eq;
 use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
 use tempfile::{NamedTempFile, TempPath};
@@ -14,25 +19,65 @@ const BINARY_PATH: &str = if cfg!(debug_assertions) {
     "../../../target/release/comm"
 };
 
-fn run_comm(first: &[&str], second: &[&str]) -> Vec<String> {
-    fn create_tempfile(data: &[&str]) -> io::Result<TempPath> {
+/// Runs comm on files with the given contents, passing `-` for files that are `None`
+/// and feeding `stdin` to it.
+fn run_comm_stdin(args: &[&str], files: &[Option<&[u8]>], stdin: &[u8]) -> Output {
+    fn create_tempfile(data: &[u8]) -> io::Result<TempPath> {
         let (mut file, path) = NamedTempFile::new()?.into_parts();
-        for line in data {
-            file.write_all(line.as_bytes())?;
-            file.write(b"\n")?;
-        }
+        file.write_all(data)?;
         file.flush()?;
         Ok(path)
     }
 
-    let first_path = create_tempfile(first).expect("failed to create temp file");
-    let second_path = create_tempfile(second).expect("failed to create temp file");
-    let output = Command::new(BINARY_PATH)
-        .args(&[first_path, second_path])
-        .output()
+    let paths = files
+        .iter()
+        .map(|data| data.map(|data| create_tempfile(data).expect("failed to create temp file")))
+        .collect::<Vec<_>>();
+    let mut child = Command::new(BINARY_PATH)
+        .args(args)
+        .args(paths.iter().map(|path| match path {
+            Some(path) => path.as_os_str(),
+            None => "-".as_ref(),
+        }))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
         .expect("failed to call comm");
+    let mut child_stdin = child.stdin.take().unwrap();
+    let stdin = stdin.to_vec();
+    // comm may exit without reading stdin, which is not an error here.
+    let writer = thread::spawn(move || child_stdin.write_all(&stdin).ok());
+    let output = child.wait_with_output().expect("failed to wait for comm");
+    writer.join().unwrap();
+    output
+}
 
-    assert!(output.status.success(), "comm process failed");
+fn run_comm_bytes(args: &[&str], files: &[&[u8]]) -> Output {
+    let files = files.iter().map(|&data| Some(data)).collect::<Vec<_>>();
+    run_comm_stdin(args, &files, b"")
+}
+
+fn run_comm_files(args: &[&str], files: &[&[&str]]) -> Output {
+    let files = files
+        .iter()
+        .map(|lines| lines.iter().map(|line| format!("{}\n", line)).collect())
+        .collect::<Vec<String>>();
+    let files = files.iter().map(String::as_bytes).collect::<Vec<_>>();
+    run_comm_bytes(args, &files)
+}
+
+fn run_comm_output(args: &[&str], first: &[&str], second: &[&str]) -> Output {
+    run_comm_files(args, &[first, second])
+}
+
+fn run_comm(args: &[&str], first: &[&str], second: &[&str]) -> Vec<String> {
+    let output = run_comm_output(args, first, second);
+    assert!(
+        output.status.success(),
+        "comm process failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
 
     let mut result: Vec<String> = String::from_utf8(output.stdout)
         .expect("comm result is not a valid utf-8")
@@ -45,7 +90,11 @@ fn run_comm(first: &[&str], second: &[&str]) -> Vec<String> {
 }
 
 fn check(first: &[&str], second: &[&str], expected_output: &[&str]) {
-    let mut output = run_comm(first, second);
+    check_with(&[], first, second, expected_output)
+}
+
+fn check_with(args: &[&str], first: &[&str], second: &[&str], expected_output: &[&str]) {
+    let mut output = run_comm(args, first, second);
     output.sort();
     let mut expected: Vec<_> = expected_output.iter().map(|s| s.to_string()).collect();
     expected.sort();
@@ -78,34 +127,1245 @@ fn test_simple() {
     check(&["", ""], &["", ""], &[""]);
 }
 
+const TOKENS: &[&str] = &[
+    "Alfa", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel", "India", "Juliett",
+    "Kilo", "Lima", "Mike", "November", "Oscar", "Papa", "Quebec", "Romeo", "Sierra", "Tango",
+    "Uniform", "Victor", "Whiskey", "X-ray", "Yankee", "Zulu",
+];
+
+fn make_random_lines(rng: &mut StdRng) -> Vec<&'static str> {
+    let mut lines = vec![];
+    for _ in 0..rng.gen_range(0..TOKENS.len()) {
+        lines.push(*TOKENS.choose(rng).unwrap());
+    }
+    lines.shuffle(rng);
+    lines
+}
+
+fn intersection<'a>(first: &[&'a str], second: &[&'a str]) -> Vec<&'a str> {
+    first
+        .iter()
+        .cloned()
+        .collect::<HashSet<_>>()
+        .intersection(&second.iter().cloned().collect())
+        .cloned()
+        .collect()
+}
+
 #[test]
 fn test_random() {
-    fn make_random_lines(rng: &mut StdRng) -> Vec<&'static str> {
-        const TOKENS: &[&str] = &[
-            "Alfa", "Bravo", "Charlie", "Delta", "Echo", "Foxtrot", "Golf", "Hotel", "India",
-            "Juliett", "Kilo", "Lima", "Mike", "November", "Oscar", "Papa", "Quebec", "Romeo",
-            "Sierra", "Tango", "Uniform", "Victor", "Whiskey", "X-ray", "Yankee", "Zulu",
-        ];
+    let mut rng = StdRng::seed_from_u64(13254252323);
+    for _ in 0..1000 {
+        let first = make_random_lines(&mut rng);
+        let second = make_random_lines(&mut rng);
+        check(&first, &second, &intersection(&first, &second));
+    }
+}
 
-        let mut lines = vec![];
-        for _ in 0..rng.gen_range(0..TOKENS.len()) {
-            lines.push(*TOKENS.choose(rng).unwrap());
+#[test]
+fn test_threads() {
+    // A memory limit that is never reached compares lines in a single hash table.
+    let reference = ["--memory-limit=1G"];
+    let mut rng = StdRng::seed_from_u64(8347591);
+    for _ in 0..200 {
+        let first = make_random_lines(&mut rng);
+        let second = make_random_lines(&mut rng);
+        let expected = run_comm(&reference, &first, &second);
+        for threads in ["1", "2", "7"] {
+            let args = ["--threads", threads];
+            assert_eq!(run_comm(&args, &first, &second), expected);
         }
-        lines.shuffle(rng);
-        lines
     }
 
-    let mut rng = StdRng::seed_from_u64(13254252323);
-    for _ in 0..1000 {
+    let first = ["1,a", "2,b", " 3,c", "1,d"];
+    let second = ["3,C", "2,B", "1,A", "2,B"];
+    let key = ["--key=1", "--field-separator=,", "--trim"];
+    for args in [&key[..], &[&key[..], &["--emit=file2"]].concat()] {
+        let expected = run_comm(&[args, &reference].concat(), &first, &second);
+        let output = run_comm(&[args, &["--threads=3"]].concat(), &first, &second);
+        assert_eq!(output, expected);
+    }
+
+    for threads in ["1", "4"] {
+        let args = ["-z", "--threads", threads];
+        let output = run_comm_bytes(&args, &[b"a\nb\0c\0\0d", b"d\0\0a\nb\0e"]);
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"d\0\0a\nb\0");
+    }
+}
+
+#[test]
+fn test_memory_limit() {
+    let temp_dir = tempfile::tempdir().expect("failed to create temp dir");
+    let temp_arg = format!("--temp-dir={}", temp_dir.path().display());
+
+    let mut rng = StdRng::seed_from_u64(4378234);
+    for limit in ["1", "64", "1K", "1M"] {
+        let args = ["--memory-limit", limit, &temp_arg];
+        check_with(
+            &args,
+            &["foo", "bar", "baz"],
+            &["bar", "foo"],
+            &["foo", "bar"],
+        );
+        check_with(&args, &[], &["foo"], &[]);
+        check_with(&args, &["", ""], &["", "foo"], &[""]);
+
+        for _ in 0..20 {
+            let first = (0..500)
+                .map(|_| *TOKENS.choose(&mut rng).unwrap())
+                .collect::<Vec<_>>();
+            let second = make_random_lines(&mut rng);
+            check_with(&args, &first, &second, &intersection(&first, &second));
+        }
+    }
+
+    let leftovers = std::fs::read_dir(temp_dir.path()).unwrap().count();
+    assert_eq!(leftovers, 0, "temporary files are not removed");
+}
+
+/// Expected output of `comm` with `columns` selected, without indentation.
+fn columns_model(
+    first: &[&str],
+    second: &[&str],
+    columns: [bool; 3],
+    multiset: bool,
+) -> Vec<String> {
+    fn count<'a>(lines: &[&'a str], multiset: bool) -> HashMap<&'a str, usize> {
+        let mut counts = HashMap::new();
+        for &line in lines {
+            let count = counts.entry(line).or_insert(0);
+            if multiset || *count == 0 {
+                *count += 1;
+            }
+        }
+        counts
+    }
+
+    let (first, second) = (count(first, multiset), count(second, multiset));
+    let mut output = vec![];
+    for (index, counts, other) in [(0, &first, &second), (1, &second, &first)] {
+        for (line, &n) in counts.iter() {
+            let m = other.get(line).copied().unwrap_or(0);
+            let indent = columns[..index].iter().filter(|&&c| c).count();
+            for _ in m..n {
+                if columns[index] {
+                    output.push(format!("{}{}", "\t".repeat(indent), line));
+                }
+            }
+            if index == 0 && columns[2] {
+                let indent = columns[..2].iter().filter(|&&c| c).count();
+                for _ in 0..n.min(m) {
+                    output.push(format!("{}{}", "\t".repeat(indent), line));
+                }
+            }
+        }
+    }
+    output
+}
+
+#[test]
+fn test_columns() {
+    let first = ["a", "b", "b", "c", "x"];
+    let second = ["b", "d", "b", "b", "c", "d"];
+    let exact = |args: &[&str], expected: &[&str]| {
+        assert_eq!(run_comm(args, &first, &second), expected);
+    };
+
+    exact(&[], &["b", "c"]);
+    exact(&["-12"], &["b", "c"]);
+    exact(&["--columns"], &["\t\tb", "\td", "\t\tc", "a", "x"]);
+    exact(&["-3"], &["\td", "a", "x"]);
+    exact(&["-1"], &["\tb", "d", "\tc"]);
+    exact(&["-2", "-3"], &["a", "x"]);
+    exact(&["-123"], &[]);
+    exact(
+        &["--columns", "--multiset"],
+        &["\t\tb", "\td", "\t\tb", "\tb", "\t\tc", "\td", "a", "x"],
+    );
+    exact(&["--multiset"], &["b", "b", "c"]);
+    exact(&["-13", "--multiset"], &["d", "b", "d"]);
+}
+
+#[test]
+fn test_columns_random() {
+    let mut rng = StdRng::seed_from_u64(9873425);
+    for _ in 0..200 {
+        let first = make_random_lines(&mut rng);
+        let second = make_random_lines(&mut rng);
+        let columns: [bool; 3] = rng.gen();
+        let multiset = rng.gen();
+
+        let mut args = vec!["--columns"];
+        for (flag, selected) in ["-1", "-2", "-3"].into_iter().zip(columns) {
+            if !selected {
+                args.push(flag);
+            }
+        }
+        if multiset {
+            args.push("--multiset");
+        }
+        let expected = columns_model(&first, &second, columns, multiset);
+        let expected = expected.iter().map(String::as_str).collect::<Vec<_>>();
+        check_with(&args, &first, &second, &expected);
+
+        args.extend(["--memory-limit", "16"]);
+        check_with(&args, &first, &second, &expected);
+    }
+}
+
+#[test]
+fn test_sorted() {
+    let mut rng = StdRng::seed_from_u64(2309487);
+    for _ in 0..200 {
+        let mut first = make_random_lines(&mut rng);
+        let mut second = make_random_lines(&mut rng);
+        first.sort_unstable();
+        second.sort_unstable();
+        let columns: [bool; 3] = rng.gen();
+        let multiset = rng.gen();
+
+        let mut args = vec!["--sorted", "--columns"];
+        for (flag, selected) in ["-1", "-2", "-3"].into_iter().zip(columns) {
+            if !selected {
+                args.push(flag);
+            }
+        }
+        if multiset {
+            args.push("--multiset");
+        }
+        let mut output = run_comm(&args, &first, &second);
+        let keys = output.iter().map(|line| line.trim_start_matches('\t'));
+        assert!(keys.clone().zip(keys.skip(1)).all(|(a, b)| a <= b));
+
+        let mut expected = columns_model(&first, &second, columns, multiset);
+        output.sort();
+        expected.sort();
+        assert_eq!(output, expected);
+    }
+
+    let first = ["apple", "Banana", "banana", "cherry"];
+    let second = ["Apple", "banana", "Cherry", "cherry"];
+    assert_eq!(
+        run_comm(
+            &["--sorted", "--collation=fold", "--columns"],
+            &first,
+            &second
+        ),
+        [
+            "\tApple",
+            "apple",
+            "Banana",
+            "\t\tbanana",
+            "\tCherry",
+            "\t\tcherry"
+        ]
+    );
+}
+
+#[test]
+fn test_sorted_unsorted_input() {
+    let output = run_comm_output(&["--sorted"], &["a", "b", "d", "c"], &["a", "b", "c", "d"]);
+    assert_eq!(output.status.code(), Some(6));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(":4: input is not sorted"), "{}", stderr);
+
+    let output = run_comm_output(&["--sorted", "-12"], &["a", "b"], &["b", "a"]);
+    assert_eq!(output.status.code(), Some(6));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(":2: input is not sorted"), "{}", stderr);
+
+    // Lines after the end of the other file are checked too, even if they are
+    // not printed.
+    for args in [&["--sorted"][..], &["--sorted", "-2"], &["--sorted", "-13"]] {
+        for (first, second) in [
+            (&["b", "a", "c"][..], &["a"][..]),
+            (&["a"], &["b", "a", "c"]),
+        ] {
+            let output = run_comm_output(args, first, second);
+            assert_eq!(output.status.code(), Some(6), "{:?}", args);
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert!(stderr.contains(":2: input is not sorted"), "{}", stderr);
+        }
+    }
+
+    let first = ["apple", "Banana"];
+    assert!(!run_comm_output(&["--sorted"], &first, &first)
+        .status
+        .success());
+    assert!(
+        run_comm_output(&["--sorted", "--collation", "fold"], &first, &first)
+            .status
+            .success()
+    );
+}
+
+#[test]
+fn test_set_operations() {
+    let files: &[&[&str]] = &[
+        &["a", "b", "c", "d"],
+        &["b", "c", "e", "c"],
+        &["c", "d", "e", "f"],
+    ];
+    let run = |args: &[&str]| {
+        let output = run_comm_files(args, files);
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(run(&[]), "c\n");
+    assert_eq!(run(&["--op", "intersection"]), "c\n");
+    assert_eq!(run(&["--op=union"]), "a\nb\nc\nd\ne\nf\n");
+    assert_eq!(run(&["--op=difference"]), "a\n");
+    assert_eq!(run(&["--op=symmetric-difference"]), "a\nc\nf\n");
+    assert_eq!(run(&["--at-least", "1"]), "a\nb\nc\nd\ne\nf\n");
+    assert_eq!(run(&["--at-least=2"]), "b\nc\nd\ne\n");
+    assert_eq!(run(&["--at-least=3"]), "c\n");
+
+    for args in [&["--at-least=0"][..], &["--at-least=4"], &["--op=xor"]] {
+        assert!(!run_comm_files(args, files).status.success());
+    }
+}
+
+#[test]
+fn test_set_operations_random() {
+    let mut rng = StdRng::seed_from_u64(582093);
+    for _ in 0..100 {
+        let files = (0..rng.gen_range(2..6))
+            .map(|_| make_random_lines(&mut rng))
+            .collect::<Vec<_>>();
+        let files = files.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let sets = files
+            .iter()
+            .map(|lines| lines.iter().copied().collect::<HashSet<_>>())
+            .collect::<Vec<_>>();
+        let count = |line: &str| sets.iter().filter(|set| set.contains(line)).count();
+
+        let at_least = rng.gen_range(1..=files.len()).to_string();
+        for args in [
+            &["--op", "intersection"][..],
+            &["--op", "union"],
+            &["--op", "difference"],
+            &["--op", "symmetric-difference"],
+            &["--at-least", &at_least],
+        ] {
+            let mut expected = vec![];
+            for &line in files.concat().iter() {
+                let present = match args[1] {
+                    "intersection" => count(line) == files.len(),
+                    "union" => true,
+                    "difference" => sets[0].contains(line) && count(line) == 1,
+                    "symmetric-difference" => count(line) % 2 == 1,
+                    _ => count(line) >= at_least.parse().unwrap(),
+                };
+                if present && !expected.contains(&line) {
+                    expected.push(line);
+                }
+            }
+
+            let output = run_comm_files(args, &files);
+            assert!(output.status.success());
+            let output = String::from_utf8(output.stdout).unwrap();
+            assert_eq!(output.lines().collect::<Vec<_>>(), expected, "{:?}", args);
+        }
+    }
+}
+
+#[test]
+fn test_normalize() {
+    let first = ["Foo  bar", "cafe\u{301}", "Straße", "ﬁle", "x"];
+    let second = [" foo bar ", "café", "STRASSE", "file", "X"];
+
+    assert_eq!(run_comm(&[], &first, &second), [] as [&str; 0]);
+    // Full case folding also expands ligatures, e.g. "ﬁ" becomes "fi".
+    assert_eq!(
+        run_comm(&["--ignore-case"], &first, &second),
+        ["Straße", "ﬁle", "x"]
+    );
+    assert_eq!(
+        run_comm(&["--collapse-whitespace", "--ignore-case"], &first, &second),
+        ["Straße", "ﬁle", "x"]
+    );
+    assert_eq!(
+        run_comm(
+            &["--collapse-whitespace", "--trim", "--ignore-case"],
+            &first,
+            &second
+        ),
+        ["Foo  bar", "Straße", "ﬁle", "x"]
+    );
+    assert_eq!(
+        run_comm(&["--normalize=nfc"], &first, &second),
+        ["cafe\u{301}"]
+    );
+    assert_eq!(
+        run_comm(&["--normalize", "nfkc"], &first, &second),
+        ["cafe\u{301}", "ﬁle"]
+    );
+    assert_eq!(
+        run_comm(&["--strip-cr"], &["a\r", "b"], &["a", "b\r"]),
+        ["a\r", "b"]
+    );
+    assert_eq!(run_comm(&[], &["a\r", "b"], &["a", "b\r"]), [] as [&str; 0]);
+
+    // Lines of the second column are printed as they occur in the second file.
+    assert_eq!(
+        run_comm(
+            &["--ignore-case", "--columns"],
+            &["A", "b"],
+            &["B", "c", "a"]
+        ),
+        ["\t\tb", "\tc", "\t\tA"]
+    );
+    assert_eq!(
+        run_comm(
+            &["--ignore-case", "--op=union"],
+            &["A", "b"],
+            &["B", "c", "a"]
+        ),
+        ["A", "b", "c"]
+    );
+    assert_eq!(
+        run_comm(
+            &["--ignore-case", "--sorted", "-3"],
+            &["a", "B"],
+            &["b", "C"]
+        ),
+        ["a", "\tC"]
+    );
+
+    let args = ["--ignore-case", "--trim", "--memory-limit=1", "--columns"];
+    let mut output = run_comm(&args, &["A", " b", "c"], &["B ", "a", "d"]);
+    output.sort();
+    assert_eq!(output, ["\t\t b", "\t\tA", "\td", "c"]);
+}
+
+#[test]
+fn test_binary_lines() {
+    let stdout = |args: &[&str], first: &[u8], second: &[u8]| {
+        let output = run_comm_bytes(args, &[first, second]);
+        assert!(output.status.success());
+        output.stdout
+    };
+
+    let first = b"caf\xe9\nB\xff\nplain\r\nlast";
+    let second = b"b\xff\ncaf\xe9\nplain\nlast\n";
+    assert_eq!(stdout(&[], first, second), b"caf\xe9\nlast\n");
+    assert_eq!(
+        stdout(&["--ignore-case", "--strip-cr"], first, second),
+        b"B\xff\ncaf\xe9\nplain\r\nlast\n"
+    );
+    assert_eq!(
+        stdout(&["--columns"], first, second),
+        b"\tb\xff\n\t\tcaf\xe9\n\tplain\n\t\tlast\nB\xff\nplain\r\n"
+    );
+    assert_eq!(
+        stdout(&["--memory-limit=1"], first, second).len(),
+        b"caf\xe9\nlast\n".len()
+    );
+
+    let (first, second) = (b"B\xff\ncaf\xe9\nplain\r\n", b"b\xff\ncaf\xe9\nplain\n");
+    let args = ["--sorted", "--collation=fold"];
+    assert_eq!(stdout(&args, first, second), b"caf\xe9\n");
+    assert_eq!(
+        stdout(
+            &[&args[..], &["--ignore-case", "--strip-cr"]].concat(),
+            first,
+            second
+        ),
+        b"B\xff\ncaf\xe9\nplain\r\n"
+    );
+}
+
+#[test]
+fn test_crlf_lines() {
+    let (crlf, lf) = (&b"a\r\nb\r\nc\r\n"[..], &b"b\nc\nd\n"[..]);
+    for args in [
+        &[][..],
+        &["--memory-limit=1"],
+        &["--op=intersection"],
+        &["--sorted"],
+    ] {
+        let output = run_comm_bytes(args, &[crlf, lf]);
+        assert!(output.status.success(), "{:?}", args);
+        assert!(output.stdout.is_empty(), "{:?}", args);
+
+        let args = [args, &["--strip-cr"]].concat();
+        let output = run_comm_bytes(&args, &[crlf, lf]);
+        assert!(output.status.success(), "{:?}", args);
+        let mut lines = output
+            .stdout
+            .split_inclusive(|&b| b == b'\n')
+            .collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(lines, [&b"b\r\n"[..], b"c\r\n"], "{:?}", args);
+    }
+}
+
+#[test]
+fn test_zero_terminated() {
+    let first = b"a\nb\0c\0d";
+    let second = b"c\0a\nb\0e\0";
+    for args in [
+        &["-z"][..],
+        &["-z", "--memory-limit=1"],
+        &["-z", "--op=intersection"],
+        &["-z", "--sorted"],
+    ] {
+        let output = run_comm_bytes(args, &[first, second]);
+        let mut records = output.stdout.split(|&b| b == 0).collect::<Vec<_>>();
+        assert_eq!(records.pop(), Some(&b""[..]), "{:?}", args);
+        records.sort();
+        match args[1..] {
+            ["--sorted"] => assert!(!output.status.success()),
+            _ => assert_eq!(records, [&b"a\nb"[..], b"c"], "{:?}", args),
+        }
+    }
+
+    let output = run_comm_bytes(&["-z", "--sorted", "-3"], &[b"a\0b\n\0", b"b\n\0c\0"]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"a\0\tc\0");
+
+    let output = run_comm_bytes(&["-z3"], &[b"a\0b\n\0", b"b\n\0c\0"]);
+    assert_eq!(output.stdout, b"\tc\0a\0");
+}
+
+#[test]
+fn test_key_fields() {
+    let first = ["1\talice", "2\tbob", "3\tcarol", "3\tcarl"];
+    let second = ["3\tC", "1\tA", "4\tD", "1\tB"];
+    check_with(&["--key=1"], &first, &second, &["1\talice", "3\tcarol"]);
+    check_with(
+        &["--key", "1", "--emit", "file2"],
+        &first,
+        &second,
+        &["3\tC", "1\tA"],
+    );
+    check_with(
+        &["--key=1", "--emit=file2", "--multiset"],
+        &first,
+        &second,
+        &["3\tC", "1\tA"],
+    );
+    check_with(
+        &["--key=1", "--columns"],
+        &first,
+        &second,
+        &["\t\t1\talice", "\t\t3\tcarol", "\t4\tD", "2\tbob"],
+    );
+    check_with(
+        &["--key=1", "--emit=file2", "--memory-limit=1"],
+        &first,
+        &second,
+        &["3\tC", "1\tA"],
+    );
+
+    let first = ["a,1,x", "b,2,y", "c,3", "d"];
+    let second = ["x,A,1", "b,Y,2", ",C,3", "d,z,"];
+    let args = ["--field-separator=,"];
+    check_with(&[&args[..], &["--key=3,2"]].concat(), &first, &second, &[]);
+    check_with(
+        &[&args[..], &["--key=3,1"]].concat(),
+        &first,
+        &second,
+        &["d"],
+    );
+    check_with(
+        &[&args[..], &["--key=2,3"], &["--ignore-case"]].concat(),
+        &["a,1,x", "b,2,y", "d"],
+        &["x,1,X", "y,2,Y", "z,,"],
+        &["a,1,x", "b,2,y", "d"],
+    );
+    check_with(
+        &[&args[..], &["--key=2", "--trim", "--op=union"]].concat(),
+        &["a, 1", "b,2 "],
+        &["c,1", "d,3"],
+        &["a, 1", "b,2 ", "d,3"],
+    );
+
+    let first = ["1 a", "2 b", "4 d"];
+    let second = ["1 A", "3 C", "4 D"];
+    let args = ["--sorted", "--key=1", "--field-separator= "];
+    assert_eq!(run_comm(&args, &first, &second), ["1 a", "4 d"]);
+    assert_eq!(
+        run_comm(
+            &[&args[..], &["--emit=file2", "-12"]].concat(),
+            &first,
+            &second
+        ),
+        ["1 A", "4 D"]
+    );
+    assert!(!run_comm_output(&args, &["2 a", "1 b"], &["3 c"])
+        .status
+        .success());
+}
+
+#[test]
+fn test_output_order() {
+    let first = ["b", "10", "a", "9", "b", "c"];
+    let second = ["c", "x", "9", "a", "10", "y", "c"];
+    let expect = |args: &[&str], expected: &[&str]| {
+        assert_eq!(run_comm(args, &first, &second), expected, "{:?}", args);
+    };
+    expect(&["--sort=lexicographic"], &["10", "9", "a", "c"]);
+    expect(&["--sort=numeric"], &["a", "c", "9", "10"]);
+    expect(&["--order=file1"], &["10", "a", "9", "c"]);
+    expect(&["--order", "file2"], &["c", "9", "a", "10"]);
+    expect(
+        &["--sort=lexicographic", "--columns"],
+        &["\t\t10", "\t\t9", "\t\ta", "b", "\t\tc", "\tx", "\ty"],
+    );
+    expect(
+        &["--order=file1", "--columns"],
+        &["b", "\t\t10", "\t\ta", "\t\t9", "\t\tc", "\tx", "\ty"],
+    );
+    expect(
+        &["--order=file2", "--columns"],
+        &["\t\tc", "\tx", "\t\t9", "\t\ta", "\t\t10", "\ty", "b"],
+    );
+    expect(
+        &["--order=file1", "--op=union"],
+        &["b", "10", "a", "9", "c", "x", "y"],
+    );
+    expect(
+        &["--order=file2", "--op=symmetric-difference"],
+        &["x", "y", "b"],
+    );
+
+    assert_eq!(
+        run_comm(
+            &["--sort=numeric", "--columns"],
+            &["1.50", " 2", "-1", "x", "-0", "007"],
+            &["1.5", "10", "-.5", "0"]
+        ),
+        ["-1", "\t-.5", "-0", "\t0", "x", "\t1.5", "1.50", " 2", "007", "\t10"]
+    );
+    assert_eq!(
+        run_comm(
+            &["--sort=numeric"],
+            &["123456789012345678901", "123456789012345678900"],
+            &["123456789012345678900", "123456789012345678901"]
+        ),
+        ["123456789012345678900", "123456789012345678901"]
+    );
+
+    // Repeated lines are ordered by column, then by the first occurrence.
+    let first = ["a", "b", "a", "a", "c"];
+    let second = ["b", "b", "a", "d", "b"];
+    let args = ["--multiset", "--columns"];
+    assert_eq!(
+        run_comm(
+            &[&args[..], &["--sort=lexicographic"]].concat(),
+            &first,
+            &second
+        ),
+        ["a", "a", "\t\ta", "\tb", "\tb", "\t\tb", "c", "\td"]
+    );
+    assert_eq!(
+        run_comm(&[&args[..], &["--order=file1"]].concat(), &first, &second),
+        ["a", "a", "\t\ta", "\t\tb", "c", "\tb", "\tb", "\td"]
+    );
+}
+
+#[test]
+fn test_output_order_random() {
+    let mut rng = StdRng::seed_from_u64(6421087);
+    for _ in 0..50 {
+        let mut first = make_random_lines(&mut rng);
+        let mut second = make_random_lines(&mut rng);
+        let mut expected = intersection(&first, &second);
+        expected.sort();
+        assert_eq!(
+            run_comm(&["--sort=lexicographic"], &first, &second),
+            expected
+        );
+
+        // Sorted inputs can be compared in every mode with the same result.
+        first.sort();
+        second.sort();
+        let columns = ["--columns", "-2"];
+        for order in [
+            &["--sort=lexicographic"][..],
+            &["--sort=numeric"],
+            &["--order=file1"],
+            &["--order=file2"],
+        ] {
+            for extra in [&[][..], &["--multiset"], &columns, &["--multiset", "-3"]] {
+                let args = [order, extra].concat();
+                let expected = run_comm(&args, &first, &second);
+                for mode in [&["--sorted"][..], &["--threads=3"]] {
+                    let output = run_comm(&[&args[..], mode].concat(), &first, &second);
+                    assert_eq!(output, expected, "{:?} {:?}", args, mode);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_stats() {
+    let first = ["a", "b", "b", "c", "A"];
+    let second = ["b", "c", "d", "d", "d"];
+    assert_eq!(
+        run_comm(&["--stats"], &first, &second),
+        [
+            "file1 lines: 5",
+            "file1 distinct: 4",
+            "file1 duplicates: 1",
+            "file1 only: 2",
+            "file2 lines: 5",
+            "file2 distinct: 3",
+            "file2 duplicates: 2",
+            "file2 only: 1",
+            "common: 2",
+            "union: 5",
+            "jaccard: 0.4",
+        ]
+    );
+    assert_eq!(
+        run_comm(
+            &["--stats", "--format=json", "--ignore-case"],
+            &first,
+            &second
+        ),
+        [concat!(
+            r#"{"file1":{"lines":5,"distinct":3,"duplicates":2,"only":1},"#,
+            r#""file2":{"lines":5,"distinct":3,"duplicates":2,"only":1},"#,
+            r#""common":2,"union":4,"jaccard":0.5}"#
+        )]
+    );
+    assert_eq!(
+        run_comm(&["--stats", "--format", "json"], &[], &[]),
+        [concat!(
+            r#"{"file1":{"lines":0,"distinct":0,"duplicates":0,"only":0},"#,
+            r#""file2":{"lines":0,"distinct":0,"duplicates":0,"only":0},"#,
+            r#""common":0,"union":0,"jaccard":1}"#
+        )]
+    );
+    assert_eq!(
+        run_comm(
+            &["--stats", "--key=1", "--field-separator=,"],
+            &["1,a", "2,b", "1,c"],
+            &["1,x", "3,y"]
+        )[8..],
+        ["common: 1", "union: 3", "jaccard: 0.3333333333333333"]
+    );
+
+    let output = run_comm_bytes(&["--stats", "-z"], &[b"a\nb\0a\nb\0c", b"a\nb\0"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("file1 lines: 3\nfile1 distinct: 2\n"));
+    assert!(stdout.ends_with("common: 1\nunion: 2\njaccard: 0.5\n"));
+}
+
+#[test]
+fn test_stats_random() {
+    let mut rng = StdRng::seed_from_u64(9052113);
+    for _ in 0..200 {
         let first = make_random_lines(&mut rng);
         let second = make_random_lines(&mut rng);
-        let answer: Vec<&str> = first
+        let first_set = first.iter().collect::<HashSet<_>>();
+        let second_set = second.iter().collect::<HashSet<_>>();
+        let common = first_set.intersection(&second_set).count();
+        let union = first_set.union(&second_set).count();
+        let jaccard = if union == 0 {
+            1.0
+        } else {
+            common as f64 / union as f64
+        };
+        let file = |lines: &[&str], set: &HashSet<_>| {
+            format!(
+                "{{\"lines\":{},\"distinct\":{},\"duplicates\":{},\"only\":{}}}",
+                lines.len(),
+                set.len(),
+                lines.len() - set.len(),
+                set.len() - common
+            )
+        };
+        let expected = format!(
+            "{{\"file1\":{},\"file2\":{},\"common\":{},\"union\":{},\"jaccard\":{}}}",
+            file(&first, &first_set),
+            file(&second, &second_set),
+            common,
+            union,
+            jaccard
+        );
+        assert_eq!(
+            run_comm(&["--stats", "--format=json"], &first, &second),
+            [expected]
+        );
+    }
+}
+
+#[test]
+fn test_fuzzy() {
+    let first = ["hello world", "foo bar baz", "unrelated", "HELLO WORLD"];
+    let second = [
+        "hallo world",
+        "foo bar baz 123",
+        "something else",
+        "hello world!",
+    ];
+    assert_eq!(
+        run_comm(&["--fuzzy=edit", "--threshold=0.7"], &first, &second),
+        [
+            "hello world\thello world!\t0.917",
+            "foo bar baz\tfoo bar baz 123\t0.733",
+        ]
+    );
+    // "foo bar baz" has 8 distinct trigrams, all of them in the 12 of its match.
+    assert_eq!(
+        run_comm(&["--fuzzy=jaccard", "--threshold", "0.6"], &first, &second),
+        [
+            "hello world\thello world!\t0.900",
+            "foo bar baz\tfoo bar baz 123\t0.667",
+        ]
+    );
+    assert_eq!(
+        run_comm(&["--fuzzy=edit", "--ignore-case"], &first, &second),
+        ["hello world\thello world!\t0.917"]
+    );
+    assert_eq!(
+        run_comm(
+            &["--fuzzy=jaccard", "--ngram=1", "--threshold=1"],
+            &["abc", "ab", "", ""],
+            &["cba", "", "ba"]
+        ),
+        ["abc\tcba\t1.000", "ab\tba\t1.000", "\t\t1.000"]
+    );
+    assert_eq!(
+        run_comm(
+            &["--fuzzy=edit", "--threshold=0.5", "--order=file2"],
+            &["abcd", "wxyz"],
+            &["wxyy", "abcc", "abdd"]
+        ),
+        ["wxyz\twxyy\t0.750", "abcd\tabcc\t0.750"]
+    );
+}
+
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in 0..=a.len() {
+        for j in 0..=b.len() {
+            distances[i][j] = match (i, j) {
+                (0, _) => j,
+                (_, 0) => i,
+                _ => (distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]))
+                    .min(distances[i - 1][j] + 1)
+                    .min(distances[i][j - 1] + 1),
+            };
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+#[test]
+fn test_fuzzy_random() {
+    let mut rng = StdRng::seed_from_u64(4410873);
+    let threshold = 0.8;
+    let mut expected_count = 0;
+    let mut found_count = 0;
+    for _ in 0..50 {
+        let phrase = |rng: &mut StdRng| {
+            (0..4)
+                .map(|_| *TOKENS.choose(rng).unwrap())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let first = (0..20).map(|_| phrase(&mut rng)).collect::<Vec<_>>();
+        let mut second = (0..10).map(|_| phrase(&mut rng)).collect::<Vec<_>>();
+        for line in first.choose_multiple(&mut rng, 10) {
+            let mut line = line.clone().into_bytes();
+            for _ in 0..rng.gen_range(0..4) {
+                let index = rng.gen_range(0..line.len());
+                match rng.gen_range(0..3) {
+                    0 => line[index] = rng.gen_range(b'a'..=b'z'),
+                    1 => line.insert(index, rng.gen_range(b'a'..=b'z')),
+                    _ => drop(line.remove(index)),
+                }
+            }
+            second.push(String::from_utf8(line).unwrap());
+        }
+        second.shuffle(&mut rng);
+
+        let similarity = |a: &str, b: &str| {
+            let longest = a.len().max(b.len());
+            1.0 - edit_distance(a.as_bytes(), b.as_bytes()) as f64 / longest as f64
+        };
+        let mut seen = HashSet::new();
+        let expected = first
             .iter()
-            .cloned()
-            .collect::<HashSet<_>>()
-            .intersection(&second.iter().cloned().collect())
-            .cloned()
-            .collect();
-        check(&first, &second, &answer);
+            .filter(|line| seen.insert(*line))
+            .filter_map(|line| {
+                let best = second
+                    .iter()
+                    .map(|other| similarity(line, other))
+                    .fold(0.0, f64::max);
+                (best >= threshold).then_some((line.as_str(), best))
+            })
+            .collect::<Vec<_>>();
+        expected_count += expected.len();
+
+        let first = first.iter().map(String::as_str).collect::<Vec<_>>();
+        let second = second.iter().map(String::as_str).collect::<Vec<_>>();
+        let output = run_comm(&["--fuzzy=edit"], &first, &second);
+        let mut expected = expected.into_iter().peekable();
+        for line in &output {
+            let fields = line.split('\t').collect::<Vec<_>>();
+            assert_eq!(fields.len(), 3, "{}", line);
+            // Pairs may be missed, but found ones are the best and in order.
+            while expected.next_if(|&(line, _)| line != fields[0]).is_some() {}
+            let (line, best) = expected.next().expect("unexpected line");
+            assert!(second.contains(&fields[1]));
+            assert_eq!(fields[2], format!("{:.3}", similarity(line, fields[1])));
+            assert_eq!(fields[2], format!("{:.3}", best));
+        }
+        found_count += output.len();
+    }
+    assert!(found_count > 0);
+    assert!(found_count * 100 >= expected_count * 95);
+}
+
+/// Returns lines of `output` in sorted order, for modes that write them in any order.
+fn sorted_lines(output: &Output) -> Vec<&[u8]> {
+    let mut lines = output
+        .stdout
+        .split_inclusive(|&b| b == b'\n')
+        .collect::<Vec<_>>();
+    lines.sort();
+    lines
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn zstd(data: &[u8]) -> Vec<u8> {
+    zstd::encode_all(data, 0).unwrap()
+}
+
+#[test]
+fn test_compressed_input() {
+    let first = b"a\nb\nc\nb\n";
+    let second = b"x\nc\nb\n";
+    type Encode = fn(&[u8]) -> Vec<u8>;
+    let encodings: [(&str, Encode); 3] =
+        [("plain", <[u8]>::to_vec), ("gzip", gzip), ("zstd", zstd)];
+    for (first_name, encode_first) in encodings {
+        for (second_name, encode_second) in encodings {
+            let files = [encode_first(first), encode_second(second)];
+            for args in [
+                &[][..],
+                &["--threads=2"],
+                &["--memory-limit=1"],
+                &["--op=intersection"],
+            ] {
+                let output = run_comm_bytes(args, &[&files[0], &files[1]]);
+                assert!(output.status.success(), "{} {}", first_name, second_name);
+                assert_eq!(
+                    sorted_lines(&output),
+                    [b"b\n", b"c\n"],
+                    "{} {} {:?}",
+                    first_name,
+                    second_name,
+                    args
+                );
+            }
+        }
+    }
+
+    // Concatenated gzip members and zstd frames are decompressed one after another.
+    let output = run_comm_bytes(
+        &["--sort=lexicographic"],
+        &[
+            &[gzip(b"a\nb\n"), gzip(b"c\n")].concat(),
+            &[zstd(b"c\n"), zstd(b"a\n")].concat(),
+        ],
+    );
+    assert_eq!(output.stdout, b"a\nc\n");
+
+    // Inputs too short for a magic number are not compressed.
+    let output = run_comm_bytes(&[], &[b"\x1f", b"\x1f"]);
+    assert_eq!(output.stdout, b"\x1f\n");
+
+    let output = run_comm_bytes(&[], &[&gzip(first)[..10], second]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_stdin() {
+    let first = b"a\nb\nc\n";
+    let second = b"c\nb\nx\n";
+    for stdin in [second.to_vec(), gzip(second), zstd(second)] {
+        let output = run_comm_stdin(&["--columns"], &[Some(first), None], &stdin);
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"\t\tc\n\t\tb\n\tx\na\n");
+    }
+    for args in [&[][..], &["--memory-limit=1"], &["--op=intersection"]] {
+        let output = run_comm_stdin(args, &[None, Some(&zstd(second))], &gzip(first));
+        assert!(output.status.success(), "{:?}", args);
+        assert_eq!(sorted_lines(&output), [b"b\n", b"c\n"], "{:?}", args);
+    }
+
+    let output = run_comm_stdin(&["--sorted"], &[None, Some(b"b\nc\nx\n")], &gzip(first));
+    assert_eq!(output.stdout, b"b\nc\n");
+
+    let output = run_comm_stdin(&["--stats"], &[Some(first), None], b"");
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("file2 lines: 0\n"));
+
+    let output = run_comm_stdin(&["--sorted"], &[Some(first), None], b"b\na\n");
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "comm: -:2: input is not sorted\n"
+    );
+
+    let output = run_comm_stdin(&[], &[None, None], first);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_fifo() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths = [dir.path().join("first"), dir.path().join("second")];
+    for path in &paths {
+        assert!(Command::new("mkfifo").arg(path).status().unwrap().success());
+    }
+
+    // Pipes can be read only once, so comm must not peek at them before reading.
+    let data = b"hello\nworld\n";
+    for args in [
+        &[][..],
+        &["--threads=2"],
+        &["--memory-limit=1M"],
+        &["--memory-limit=1"],
+    ] {
+        for second in [data.to_vec(), gzip(data)] {
+            let output = thread::scope(|scope| {
+                scope.spawn(|| fs::write(&paths[0], data).unwrap());
+                scope.spawn(|| fs::write(&paths[1], &second).unwrap());
+                run_comm_paths(args, &[&paths[0], &paths[1]])
+            });
+            assert!(output.status.success(), "{:?}", args);
+            assert_eq!(
+                sorted_lines(&output),
+                [b"hello\n", b"world\n"],
+                "{:?}",
+                args
+            );
+        }
+    }
+}
+
+#[test]
+fn test_invalid_args() {
+    for args in [
+        &["file"][..],
+        &["--memory-limit=0", "a", "b"],
+        &["--memory-limit=12X", "a", "b"],
+        &["--temp-dir=/tmp", "a", "b"],
+        &["--unknown", "a", "b"],
+        &["-4", "a", "b"],
+        &["--collation=fold", "a", "b"],
+        &["--sorted", "--collation=locale", "a", "b"],
+        &["--sorted", "--memory-limit=1M", "a", "b"],
+        &["--op=union", "-3", "a", "b"],
+        &["--multiset", "a", "b", "c"],
+        &["--op=union", "--at-least=1", "a", "b"],
+        &["--normalize=nfd", "a", "b"],
+        &["--key=0", "a", "b"],
+        &["--key=1,", "a", "b"],
+        &["--field-separator=,", "a", "b"],
+        &["--key=1", "--field-separator=ab", "a", "b"],
+        &["--emit=file3", "a", "b"],
+        &["--emit=file2", "--op=union", "a", "b"],
+        &["--threads=0", "a", "b"],
+        &["--threads=2", "--sorted", "a", "b"],
+        &["--threads=2", "--memory-limit=1M", "a", "b"],
+        &["--threads=2", "a", "b", "c"],
+        &["--sort=natural", "a", "b"],
+        &["--order=file3", "a", "b"],
+        &["--sort=numeric", "--order=file1", "a", "b"],
+        &["--sort=numeric", "--memory-limit=1M", "a", "b"],
+        &["--format=json", "a", "b"],
+        &["--stats", "--format=xml", "a", "b"],
+        &["--stats", "-3", "a", "b"],
+        &["--stats", "--sorted", "a", "b"],
+        &["--stats", "a", "b", "c"],
+        &["--fuzzy=soundex", "a", "b"],
+        &["--threshold=0.5", "a", "b"],
+        &["--fuzzy=edit", "--threshold=0", "a", "b"],
+        &["--fuzzy=edit", "--threshold=1.5", "a", "b"],
+        &["--fuzzy=edit", "--ngram=0", "a", "b"],
+        &["--fuzzy=edit", "--columns", "a", "b"],
+        &["--fuzzy=edit", "--sorted", "a", "b"],
+        &["--fuzzy=edit", "a", "b", "c"],
+    ] {
+        let output = Command::new(BINARY_PATH)
+            .args(args)
+            .output()
+            .expect("failed to call comm");
+        assert_eq!(output.status.code(), Some(2), "comm accepted {:?}", args);
+        assert!(output.stdout.is_empty());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(stderr.lines().count(), 1, "{}", stderr);
+        assert!(stderr.ends_with("(see 'comm --help')\n"), "{}", stderr);
+    }
+
+    // More than two files are not a usage error, they select set operations.
+    let output = run_comm_files(&[], &[&["a", "b"], &["b"], &["b", "c"]]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"b\n");
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+fn run_comm_paths(args: &[&str], paths: &[&Path]) -> Output {
+    Command::new(BINARY_PATH)
+        .args(args)
+        .args(paths)
+        .output()
+        .expect("failed to call comm")
+}
+
+fn create_file(data: &[u8]) -> TempPath {
+    let mut file = NamedTempFile::new().unwrap();
+    file.write_all(data).unwrap();
+    file.into_temp_path()
+}
+
+/// Checks that comm failed with `code` and a single line of stderr that starts
+/// with `message`.
+fn assert_error(output: &Output, code: i32, message: &str) {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(code), "{}", stderr);
+    assert!(stderr.starts_with(message), "{}", stderr);
+    assert_eq!(stderr.lines().count(), 1, "{}", stderr);
+}
+
+const MODES: &[&[&str]] = &[
+    &[],
+    &["--columns"],
+    &["--threads=2"],
+    &["--memory-limit=1"],
+    &["--sorted"],
+    &["--op=union"],
+    &["--stats"],
+];
+
+#[test]
+fn test_help() {
+    for args in [
+        &["--help"][..],
+        &["--help", "a"],
+        &["-3", "--help", "a", "b"],
+    ] {
+        let output = Command::new(BINARY_PATH)
+            .args(args)
+            .output()
+            .expect("failed to call comm");
+        assert!(output.status.success(), "{:?}", args);
+        assert!(output.stdout.starts_with(b"Usage: comm "), "{:?}", args);
+        assert!(output.stderr.is_empty(), "{:?}", args);
+    }
+}
+
+#[test]
+fn test_missing_file() {
+    let file = create_file(b"a\n");
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing");
+    let message = format!("comm: {}: no such file or directory\n", missing.display());
+    for args in MODES {
+        for paths in [[file.as_ref(), missing.as_path()], [&missing, &file]] {
+            let output = run_comm_paths(args, &paths);
+            assert_error(&output, 3, &message);
+            assert_eq!(String::from_utf8_lossy(&output.stderr), message);
+        }
+    }
+}
+
+#[test]
+fn test_permission_denied() {
+    let file = create_file(b"a\n");
+    let denied = create_file(b"a\n");
+    fs::set_permissions(&denied, fs::Permissions::from_mode(0o000)).unwrap();
+    if File::open(&denied).is_ok() {
+        // Permissions are not checked for root.
+        return;
+    }
+    let message = format!("comm: {}: permission denied\n", denied.display());
+    for args in MODES {
+        let output = run_comm_paths(args, &[&file, &denied]);
+        assert_error(&output, 4, &message);
+    }
+}
+
+#[test]
+fn test_read_error() {
+    let file = create_file(b"a\n");
+    let data = (0..100_000)
+        .map(|i| format!("{:06}\n", i))
+        .collect::<String>();
+    let compressed = gzip(data.as_bytes());
+    let truncated = create_file(&compressed[..compressed.len() / 2]);
+    let dir = tempfile::tempdir().unwrap();
+    for path in [truncated.as_ref(), dir.path()] {
+        let message = format!("comm: {}: read error: ", path.display());
+        for args in MODES {
+            let output = run_comm_paths(args, &[&file, path]);
+            assert_error(&output, 5, &message);
+            let output = run_comm_paths(args, &[path, &file]);
+            assert_error(&output, 5, &message);
+        }
+    }
+}
+
+#[test]
+fn test_temp_file_error() {
+    let file = create_file(b"a\nb\n");
+    let dir = tempfile::tempdir().unwrap();
+    let temp_dir = format!("--temp-dir={}", dir.path().join("missing").display());
+    let output = run_comm_paths(&["--memory-limit=1", &temp_dir], &[&file, &file]);
+    assert_error(&output, 7, "comm: temporary file error: ");
+}
+
+#[test]
+fn test_write_error() {
+    let file = create_file(b"a\nb\n");
+    for args in MODES {
+        let output = Command::new(BINARY_PATH)
+            .args(*args)
+            .args([&file, &file])
+            .stdout(File::create("/dev/full").unwrap())
+            .output()
+            .expect("failed to call comm");
+        assert_error(&output, 8, "comm: write error: ");
+    }
+}
+
+#[test]
+fn test_broken_pipe() {
+    let data = (0..100_000)
+        .map(|i| format!("{:06}\n", i))
+        .collect::<String>();
+    let file = create_file(data.as_bytes());
+    for args in MODES {
+        let mut child = Command::new(BINARY_PATH)
+            .args(*args)
+            .args([&file, &file])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .expect("failed to call comm");
+        drop(child.stdout.take());
+        let output = child.wait_with_output().expect("failed to wait for comm");
+        assert_eq!(output.status.code(), Some(141), "{:?}", args);
+        assert!(output.stderr.is_empty(), "{:?}", args);
     }
 }
@@ -0,0 +1,181 @@
+use std::collections::HashSet;
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    heuristic::{Heuristic, LinearConflict},
+    is_solvable,
+    search::{solve_with, Algorithm},
+    Board, Tile,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Boards with at most this many cells are small enough to enumerate with BFS.
+const ENUMERABLE_CELLS: usize = 9;
+
+/// Number of random walks `board_at_distance` tries on large boards before giving up.
+const WALK_ATTEMPTS: usize = 100;
+
+/// Returns a board chosen uniformly at random among all solvable `rows` x `cols` boards.
+///
+/// # Panics
+///
+/// Panics if the board has no cells or more than 256 cells.
+pub fn random_board<R: Rng + ?Sized>(rows: usize, cols: usize, rng: &mut R) -> Board {
+    if rows == 1 || cols == 1 {
+        // Tiles of a single line never change their order, only the empty tile moves.
+        let empty = rng.gen_range(0..rows * cols);
+        let mut tiles = (1..rows * cols)
+            .map(|n| Tile::new(Some(n as u8)))
+            .collect::<Vec<_>>();
+        tiles.insert(empty, Tile::empty());
+        return Board::from_tiles(rows, cols, tiles);
+    }
+
+    let mut tiles = (1..rows * cols)
+        .map(|n| Tile::new(Some(n as u8)))
+        .chain([Tile::empty()])
+        .collect::<Vec<_>>();
+    tiles.shuffle(rng);
+
+    let mut board = Board::from_tiles(rows, cols, tiles);
+    if !is_solvable(&board) {
+        // Swapping two numbered tiles flips solvability and maps unsolvable boards
+        // one-to-one onto solvable ones, so the result stays uniform.
+        let values = board.values();
+        let mut numbered = (0..rows * cols).filter(|&i| values[i] != 0);
+        let (a, b) = (numbered.next().unwrap(), numbered.next().unwrap());
+        board.swap(a / cols, a % cols, b / cols, b % cols);
+    }
+    board
+}
+
+/// Returns a random `rows` x `cols` board whose optimal solution takes exactly
+/// `distance` moves, or `None` if no such board was found.
+///
+/// Boards with up to 9 cells are chosen uniformly among all boards at this distance,
+/// and `None` means that there are no such boards. Larger boards are built by a random
+/// walk away from the final configuration that only takes moves increasing the optimal
+/// distance; such boards are not uniformly distributed, and `None` may be returned
+/// if every walk got stuck.
+pub fn board_at_distance<R: Rng + ?Sized>(
+    rows: usize,
+    cols: usize,
+    distance: usize,
+    rng: &mut R,
+) -> Option<Board> {
+    let solved = Board::solved(rows, cols);
+    if rows * cols <= ENUMERABLE_CELLS {
+        let mut visited = HashSet::from([solved.clone()]);
+        let mut layer = vec![solved];
+        for _ in 0..distance {
+            let mut next_layer = Vec::new();
+            for board in layer.iter() {
+                for next in board.neighbours() {
+                    if visited.insert(next.clone()) {
+                        next_layer.push(next);
+                    }
+                }
+            }
+            layer = next_layer;
+        }
+        return layer.choose(rng).cloned();
+    }
+
+    'walk: for _ in 0..WALK_ATTEMPTS {
+        let mut board = solved.clone();
+        for current in 0..distance {
+            let mut candidates = board.neighbours();
+            candidates.shuffle(rng);
+            let next = candidates
+                .into_iter()
+                .find(|next| optimal_length(next, &LinearConflict) == Some(current + 1));
+            match next {
+                Some(next) => board = next,
+                None => continue 'walk,
+            }
+        }
+        return Some(board);
+    }
+    None
+}
+
+fn optimal_length<H: Heuristic + ?Sized>(board: &Board, heuristic: &H) -> Option<usize> {
+    solve_with(board.clone(), heuristic, Algorithm::IdaStar).map(|s| s.moves.len())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Difficulty of a board relative to the hardest boards of the same size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Difficulty {
+    /// Less than a quarter of the maximal distance.
+    Easy,
+    /// Less than a half of the maximal distance.
+    Medium,
+    /// Less than three quarters of the maximal distance.
+    Hard,
+    /// At least three quarters of the maximal distance.
+    Expert,
+}
+
+impl Difficulty {
+    /// Returns the difficulty of a solution of `length` moves on a `rows` x `cols` board,
+    /// or `None` if the maximal distance for this size is unknown.
+    pub fn from_length(length: usize, rows: usize, cols: usize) -> Option<Self> {
+        let max = diameter(rows, cols)?;
+        Some(match 4 * length {
+            l if l < max => Difficulty::Easy,
+            l if l < 2 * max => Difficulty::Medium,
+            l if l < 3 * max => Difficulty::Hard,
+            _ => Difficulty::Expert,
+        })
+    }
+
+    /// Returns the lowercase name of this difficulty.
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+            Difficulty::Expert => "expert",
+        }
+    }
+}
+
+/// Returns the maximal optimal distance over all solvable `rows` x `cols` boards,
+/// if it is known.
+pub fn diameter(rows: usize, cols: usize) -> Option<usize> {
+    let (small, large) = (rows.min(cols), rows.max(cols));
+    match (small, large) {
+        (1, n) => Some(n - 1),
+        (2, 2) => Some(6),
+        (2, 3) => Some(21),
+        (2, 4) => Some(36),
+        (3, 3) => Some(31),
+        (3, 4) => Some(53),
+        (4, 4) => Some(80),
+        _ => None,
+    }
+}
+
+/// Result of grading a board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Grade {
+    /// Number of moves in the optimal solution.
+    pub length: usize,
+    /// `None` if the maximal distance for this board size is unknown.
+    pub difficulty: Option<Difficulty>,
+}
+
+/// Grades `board` by the length of its optimal solution, found with IDA* guided
+/// by `heuristic`. Returns `None` if the board is unsolvable.
+pub fn grade<H: Heuristic + ?Sized>(board: &Board, heuristic: &H) -> Option<Grade> {
+    let length = optimal_length(board, heuristic)?;
+    let (rows, cols) = board.size();
+    Some(Grade {
+        length,
+        difficulty: Difficulty::from_length(length, rows, cols),
+    })
+}
@@ -0,0 +1,118 @@
+use crate::Board;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Estimates the number of moves required to solve a board.
+///
+/// Implementations must be admissible, i.e. never overestimate the real distance
+/// to the final configuration, otherwise informed search may return non-optimal paths.
+pub trait Heuristic {
+    /// Returns a lower bound on the number of moves that solve `board`.
+    fn estimate(&self, board: &Board) -> u32;
+}
+
+/// Returns `(row, col)` where tile `n` is located in the final configuration
+/// of a board with `cols` columns.
+fn goal_position(n: u8, cols: usize) -> (usize, usize) {
+    let index = n as usize - 1;
+    (index / cols, index % cols)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Counts tiles that are not on their final position.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MisplacedTiles;
+
+impl Heuristic for MisplacedTiles {
+    fn estimate(&self, board: &Board) -> u32 {
+        let (rows, cols) = board.size();
+        let mut misplaced = 0;
+        for r in 0..rows {
+            for c in 0..cols {
+                if let Some(n) = board.get(r, c).number() {
+                    if goal_position(n, cols) != (r, c) {
+                        misplaced += 1;
+                    }
+                }
+            }
+        }
+        misplaced
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Sums Manhattan distances between each tile and its final position.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Manhattan;
+
+impl Heuristic for Manhattan {
+    fn estimate(&self, board: &Board) -> u32 {
+        let (rows, cols) = board.size();
+        let mut distance = 0;
+        for r in 0..rows {
+            for c in 0..cols {
+                if let Some(n) = board.get(r, c).number() {
+                    let (gr, gc) = goal_position(n, cols);
+                    distance += (r.abs_diff(gr) + c.abs_diff(gc)) as u32;
+                }
+            }
+        }
+        distance
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Manhattan distance plus two moves for every tile that has to leave its goal line
+/// to let another tile of the same line pass.
+///
+/// Two tiles are in linear conflict if both are in their goal row (or column),
+/// but in the reversed order. For every line, the minimal number of tiles to move
+/// out of the way equals the line length minus the longest increasing subsequence
+/// of goal positions.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LinearConflict;
+
+impl LinearConflict {
+    /// Returns the number of tiles that must leave a line so that the rest are ordered.
+    fn line_conflicts(goals: &[usize]) -> u32 {
+        let mut lis = vec![1; goals.len()];
+        for i in 0..goals.len() {
+            for j in 0..i {
+                if goals[j] < goals[i] {
+                    lis[i] = lis[i].max(lis[j] + 1);
+                }
+            }
+        }
+        let longest = lis.into_iter().max().unwrap_or(0);
+        (goals.len() - longest) as u32
+    }
+}
+
+impl Heuristic for LinearConflict {
+    fn estimate(&self, board: &Board) -> u32 {
+        let (rows, cols) = board.size();
+        let mut conflicts = 0;
+        for r in 0..rows {
+            let goals = (0..cols)
+                .filter_map(|c| board.get(r, c).number())
+                .map(|n| goal_position(n, cols))
+                .filter(|&(gr, _)| gr == r)
+                .map(|(_, gc)| gc)
+                .collect::<Vec<_>>();
+            conflicts += Self::line_conflicts(&goals);
+        }
+        for c in 0..cols {
+            let goals = (0..rows)
+                .filter_map(|r| board.get(r, c).number())
+                .map(|n| goal_position(n, cols))
+                .filter(|&(_, gc)| gc == c)
+                .map(|(gr, _)| gr)
+                .collect::<Vec<_>>();
+            conflicts += Self::line_conflicts(&goals);
+        }
+        Manhattan.estimate(board) + 2 * conflicts
+    }
+}
@@ -1,10 +1,27 @@
 #![forbid(unsafe_code)]
 
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    str::FromStr,
+};
+use thiserror::Error;
+
+mod generate;
+mod heuristic;
+mod moves;
+mod pdb;
+mod search;
+
+pub use generate::{board_at_distance, diameter, grade, random_board, Difficulty, Grade};
+pub use heuristic::{Heuristic, LinearConflict, Manhattan, MisplacedTiles};
+pub use moves::{format_moves, Move, ReplayError};
+pub use pdb::{PatternDatabase, PdbError, MAX_PATTERN_TILES, MAX_TABLE_SIZE};
+pub use search::{solve_with, Algorithm, Solution};
 
 ////////////////////////////////////////////////////////////////////////////////
 
-/// Represents a tile on a board. A tile can either be empty or a number from 1 to 8.
+/// Represents a tile on a board. A tile can either be empty or a positive number.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Tile(u8);
 
@@ -13,14 +30,14 @@ impl Tile {
     ///
     /// # Arguments
     ///
-    /// * `maybe_value` - Some(1..=8) or None.
+    /// * `maybe_value` - Some(1..=255) or None.
     ///
     /// # Panics
     ///
-    /// Panics if value is 0 or > 8.
+    /// Panics if value is 0.
     pub fn new(maybe_value: Option<u8>) -> Self {
         if let Some(n) = maybe_value {
-            assert!(n > 0u8 && n < 9u8, "invalid tile value: {}", n);
+            assert!(n > 0u8, "invalid tile value: {}", n);
             Self(n)
         } else {
             Self(0)
@@ -47,115 +64,396 @@ impl Tile {
     }
 }
 
+/// Formats a tile as its number, or as `.` if it is empty.
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.number() {
+            Some(n) => write!(f, "{}", n),
+            None => write!(f, "."),
+        }
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
-/// Represents a 3x3 board of tiles.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Represents a rectangular board of tiles.
+///
+/// A board with `rows * cols` cells contains every tile from 1 to `rows * cols - 1`
+/// exactly once and a single empty tile.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Board {
-    tiles: [[Tile; 3]; 3],
+    rows: usize,
+    cols: usize,
+    tiles: Vec<Tile>,
 }
 
 impl Board {
-    /// Creates a new `Board` from a 3x3 matrix if `Tile`s.
+    /// Creates a new `Board` from a matrix of `Tile`s.
     ///
     /// # Panics
     ///
-    /// Panics if `tiles` contains more than one instance if some tile.
-    pub fn new(tiles: [[Tile; 3]; 3]) -> Self {
-        let set = tiles.iter().flatten().cloned().collect::<HashSet<_>>();
-        assert_eq!(set.len(), 9, "tiles have duplicate values {:?}", tiles);
-        Self {
-            tiles
+    /// Panics if `tiles` does not represent a valid `Board`.
+    pub fn new<const R: usize, const C: usize>(tiles: [[Tile; C]; R]) -> Self {
+        Self::from_tiles(R, C, tiles.iter().flatten().cloned().collect())
+    }
+
+    /// Creates a new `Board` from `tiles` listed in row-major order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tiles.len() != rows * cols`, if the board has more than 256 cells,
+    /// or if `tiles` is not a permutation of tiles from 1 to `rows * cols - 1`
+    /// and a single empty tile.
+    pub fn from_tiles(rows: usize, cols: usize, tiles: Vec<Tile>) -> Self {
+        assert!(
+            rows > 0 && cols > 0 && rows * cols <= 256,
+            "invalid board size: {}x{}",
+            rows,
+            cols,
+        );
+        assert_eq!(tiles.len(), rows * cols, "invalid number of tiles {:?}", tiles);
+
+        let mut seen = vec![false; tiles.len()];
+        for tile in tiles.iter() {
+            let index = tile.number().unwrap_or(0) as usize;
+            assert!(
+                index < seen.len() && !seen[index],
+                "tiles have duplicate or out of range values {:?}",
+                tiles
+            );
+            seen[index] = true;
         }
+
+        Self { rows, cols, tiles }
+    }
+
+    /// Creates a `rows` x `cols` board in the final configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the board has no cells or more than 256 cells.
+    pub fn solved(rows: usize, cols: usize) -> Self {
+        let tiles = (1..rows * cols)
+            .map(|n| Tile::new(Some(n as u8)))
+            .chain([Tile::empty()])
+            .collect();
+        Self::from_tiles(rows, cols, tiles)
+    }
+
+    /// Returns `(rows, cols)` of this board.
+    pub fn size(&self) -> (usize, usize) {
+        (self.rows, self.cols)
     }
 
     /// Returns a tile on a given `row` and `col`.
     ///
     /// # Panics
     ///
-    /// Panics if `row` or `col` > 2.
+    /// Panics if `row` or `col` is out of the board.
     pub fn get(&self, row: usize, col: usize) -> Tile {
-        self.tiles[row][col]
+        assert!(row < self.rows && col < self.cols, "out of board: ({}, {})", row, col);
+        self.tiles[row * self.cols + col]
     }
 
     /// Swaps two given tiles.
     ///
     /// # Panics
     ///
-    /// Panics if some of `r1`, `r2`, `c1` or `c2` > 2.
+    /// Panics if some of `(r1, c1)` or `(r2, c2)` is out of the board.
     pub fn swap(&mut self, r1: usize, c1: usize, r2: usize, c2: usize) {
-        let tile = self.tiles[r1][c1];
-        self.tiles[r1][c1] = self.tiles[r2][c2];
-        self.tiles[r2][c2] = tile;
+        assert!(r1 < self.rows && c1 < self.cols, "out of board: ({}, {})", r1, c1);
+        assert!(r2 < self.rows && c2 < self.cols, "out of board: ({}, {})", r2, c2);
+        self.tiles.swap(r1 * self.cols + c1, r2 * self.cols + c2);
     }
 
     /// Parses `Board` from string.
     ///
     /// # Arguments
     ///
-    /// * `s` must be a string in the following format:
-    ///
-    /// '''
-    /// .12
-    /// 345
-    /// 678
-    /// '''
+    /// * `s` must be a string in the format accepted by `str::parse::<Board>`.
     ///
     /// # Panics
     ///
     /// Panics of `s` is the wrong format or does not represent a valid `Board`.
     pub fn from_string(s: &str) -> Self {
-        let len: usize = s.len();
-        assert_eq!(len, 12, "invalid input: {}", s);
-
-        let mut tiles = [[Tile::empty(); 3]; 3];
-        for r in 0..3 {
-            for (c, chr) in s.bytes().skip(r * 4).take(3).enumerate() {
-                if chr > b'0' && chr < b'9' {
-                    tiles[r][c] = Tile::new(Some(chr - b'0'));
-                }
-            }
-        }
-
-        Self::new(tiles)
+        s.parse().unwrap_or_else(|err| panic!("invalid input: {}", err))
     }
 
-    /// Returns a string representation of this board in the following format:
+    /// Returns true if this board is in the final configuration, i.e. tiles are
+    /// ordered row by row and the empty tile is in the bottom right corner:
     ///
     /// '''
-    /// .12
-    /// 345
-    /// 678
+    /// 123
+    /// 456
+    /// 78.
     /// '''
-    pub fn to_string(&self) -> String {
-        let mut str = String::new();
-        for r in 0..3 {
-            for c in 0..3 {
-                if let Some(n) = self.get(r, c).number() {
-                    str.push((b'0' + n) as char);
-                } else {
-                    str.push('.');
+    pub fn is_solved(&self) -> bool {
+        self.tiles
+            .iter()
+            .enumerate()
+            .all(|(i, tile)| match tile.number() {
+                Some(n) => n as usize == i + 1,
+                None => i + 1 == self.tiles.len(),
+            })
+    }
+
+    /// Returns `(row, col)` of the empty tile.
+    pub fn empty_position(&self) -> (usize, usize) {
+        let index = self
+            .tiles
+            .iter()
+            .position(Tile::is_empty)
+            .expect("board always has an empty tile");
+        (index / self.cols, index % self.cols)
+    }
+
+    /// Returns all boards reachable from this one by swapping the empty tile
+    /// with one of its adjacent tiles.
+    pub fn neighbours(&self) -> Vec<Board> {
+        Move::ALL
+            .into_iter()
+            .filter_map(|mv| self.apply(mv))
+            .collect()
+    }
+
+    /// Returns tile values in row-major order, where 0 stands for the empty tile.
+    pub fn values(&self) -> Vec<u8> {
+        self.tiles
+            .iter()
+            .map(|tile| tile.number().unwrap_or(0))
+            .collect()
+    }
+}
+
+/// Formats `Board` in the format accepted by `Board::from_string`.
+/// Tiles are separated by spaces only if some of them take more than one digit.
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = (self.tiles.len() - 1).to_string().len();
+        for row in self.tiles.chunks(self.cols) {
+            for (c, tile) in row.iter().enumerate() {
+                if width > 1 && c > 0 {
+                    write!(f, " ")?;
                 }
+                write!(f, "{:>width$}", tile.to_string(), width = width)?;
             }
-            str.push('\n');
+            writeln!(f)?;
         }
-        str
+        Ok(())
     }
+}
 
-    // You might want to add some more methods here.
+////////////////////////////////////////////////////////////////////////////////
 
-    pub fn is_solved(&self) -> bool {
-        let set = self.tiles.iter().flatten().cloned().collect::<HashSet<_>>();
-        for (i, tile) in set.iter().enumerate() {
-            if let Some(n) = tile.number() {
-                if n != i as u8 + 1 {
-                    return false;
-                }
-            } else if i + 1 == set.len() {
-                return true;
+/// Error returned when a string does not represent a valid `Board`.
+///
+/// Lines and columns are counted from 1.
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+pub enum ParseBoardError {
+    #[error("line {line}: expected {expected} tiles, found {found}")]
+    WrongDimensions {
+        line: usize,
+        expected: usize,
+        found: usize,
+    },
+    #[error("board is empty")]
+    Empty,
+    #[error("board of size {rows}x{cols} is too large")]
+    TooLarge { rows: usize, cols: usize },
+    #[error("line {line}, column {column}: unexpected character {chr:?}")]
+    BadCharacter {
+        line: usize,
+        column: usize,
+        chr: char,
+    },
+    #[error("line {line}, column {column}: tile {tile} does not fit on the board")]
+    TileOutOfRange {
+        line: usize,
+        column: usize,
+        tile: String,
+    },
+    #[error("line {line}, column {column}: duplicate tile {tile}")]
+    DuplicateTile {
+        line: usize,
+        column: usize,
+        tile: Tile,
+    },
+    #[error("board has no empty tile")]
+    MissingBlank,
+}
+
+impl ParseBoardError {
+    /// Shifts reported line numbers by `offset`, e.g. when the board was cut out
+    /// of a larger input.
+    pub fn offset_lines(self, offset: usize) -> Self {
+        use ParseBoardError::*;
+        match self {
+            WrongDimensions {
+                line,
+                expected,
+                found,
+            } => WrongDimensions {
+                line: line + offset,
+                expected,
+                found,
+            },
+            BadCharacter { line, column, chr } => BadCharacter {
+                line: line + offset,
+                column,
+                chr,
+            },
+            TileOutOfRange { line, column, tile } => TileOutOfRange {
+                line: line + offset,
+                column,
+                tile,
+            },
+            DuplicateTile { line, column, tile } => DuplicateTile {
+                line: line + offset,
+                column,
+                tile,
+            },
+            Empty | TooLarge { .. } | MissingBlank => self,
+        }
+    }
+}
+
+/// Parses `Board` from a string with one line per row. If every tile fits in a single
+/// digit, tiles can be written without separators:
+///
+/// '''
+/// .12
+/// 345
+/// 678
+/// '''
+///
+/// Otherwise, tiles are separated by whitespace:
+///
+/// '''
+///  1  2  3  4
+///  5  6  7  8
+///  9 10 11 12
+/// 13 14 15  .
+/// '''
+///
+/// The empty tile is written as `.`. Trailing empty lines are ignored.
+impl FromStr for Board {
+    type Err = ParseBoardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .skip_while(|(_, line)| line.trim().is_empty())
+            .map(|(i, line)| (i + 1, line))
+            .collect::<Vec<_>>();
+        while lines.last().is_some_and(|(_, line)| line.trim().is_empty()) {
+            lines.pop();
+        }
+        let separated = lines
+            .iter()
+            .any(|(_, line)| line.trim().contains(char::is_whitespace));
+
+        // Tiles together with their (line, column) in the input.
+        let mut tiles = Vec::<(Tile, usize, usize)>::new();
+        let mut cols = None;
+        for &(number, line) in lines.iter() {
+            let row = parse_line(line, number, separated)?;
+            let expected = *cols.get_or_insert(row.len());
+            if row.len() != expected {
+                return Err(ParseBoardError::WrongDimensions {
+                    line: number,
+                    expected,
+                    found: row.len(),
+                });
+            }
+            tiles.extend(row);
+        }
+
+        let (rows, cols) = (lines.len(), cols.ok_or(ParseBoardError::Empty)?);
+        if rows * cols > 256 {
+            return Err(ParseBoardError::TooLarge { rows, cols });
+        }
+        if tiles.iter().all(|(tile, _, _)| !tile.is_empty()) {
+            return Err(ParseBoardError::MissingBlank);
+        }
+
+        let mut seen = vec![false; tiles.len()];
+        for &(tile, line, column) in tiles.iter() {
+            let index = tile.number().unwrap_or(0) as usize;
+            if index >= seen.len() {
+                return Err(ParseBoardError::TileOutOfRange {
+                    line,
+                    column,
+                    tile: tile.to_string(),
+                });
+            }
+            if seen[index] {
+                return Err(ParseBoardError::DuplicateTile { line, column, tile });
             }
+            seen[index] = true;
         }
-        false
+
+        Ok(Self {
+            rows,
+            cols,
+            tiles: tiles.into_iter().map(|(tile, _, _)| tile).collect(),
+        })
+    }
+}
+
+/// Parses tiles of the `number`-th line together with their (line, column) positions.
+fn parse_line(
+    line: &str,
+    number: usize,
+    separated: bool,
+) -> Result<Vec<(Tile, usize, usize)>, ParseBoardError> {
+    let chars = line.trim_end().chars().enumerate().map(|(i, chr)| (i + 1, chr));
+    if !separated {
+        return chars
+            .map(|(column, chr)| {
+                let tile = parse_token(&chr.to_string(), number, column)?;
+                Ok((tile, number, column))
+            })
+            .collect();
+    }
+
+    let mut row = Vec::new();
+    let mut token: Option<(usize, String)> = None;
+    for (column, chr) in chars.chain([(0, ' ')]) {
+        if chr.is_whitespace() {
+            if let Some((start, text)) = token.take() {
+                row.push((parse_token(&text, number, start)?, number, start));
+            }
+        } else {
+            token.get_or_insert_with(|| (column, String::new())).1.push(chr);
+        }
+    }
+    Ok(row)
+}
+
+/// Parses a single tile, which is either `.` or a number without leading zeros.
+fn parse_token(token: &str, line: usize, column: usize) -> Result<Tile, ParseBoardError> {
+    if token == "." {
+        return Ok(Tile::empty());
+    }
+    let bad_character = token
+        .chars()
+        .enumerate()
+        .find(|&(i, chr)| !chr.is_ascii_digit() || (i == 0 && chr == '0'));
+    if let Some((i, chr)) = bad_character {
+        return Err(ParseBoardError::BadCharacter {
+            line,
+            column: column + i,
+            chr,
+        });
+    }
+    match token.parse::<u8>() {
+        Ok(n) => Ok(Tile::new(Some(n))),
+        Err(_) => Err(ParseBoardError::TileOutOfRange {
+            line,
+            column,
+            tile: token.to_string(),
+        }),
     }
 }
 
@@ -164,36 +462,117 @@ impl Board {
 /// Returns the shortest sequence of moves that solves this board.
 /// That is, a sequence of boards such that each consecutive board can be obtained from
 /// the previous one via a single swap of an empty tile with some adjacent tile,
-/// and the final board in the sequence is
+/// and the final board in the sequence is `Board::solved(rows, cols)` of the same
+/// size as `start`: tiles in increasing order followed by the empty tile, e.g. for
+/// a 2x3 board
 ///
 /// '''
 /// 123
-/// 456
-/// 78.
+/// 45.
 /// '''
 ///
 /// If the board is unsolvable, returns `None`. If the board is already solved,
 /// returns an empty vector.
 pub fn solve(start: Board) -> Option<Vec<Board>> {
-    if start.is_solved() {
+    let (rows, cols) = start.size();
+    solve_to(start, &Board::solved(rows, cols))
+}
+
+/// Same as `solve`, but the final board in the sequence is `goal` instead of
+/// the final configuration, e.g.
+///
+/// '''
+/// .12
+/// 345
+/// 678
+/// '''
+///
+/// Returns `None` if `goal` cannot be reached from `start`.
+pub fn solve_to(start: Board, goal: &Board) -> Option<Vec<Board>> {
+    if start == *goal {
         return Some(vec![]);
     }
-    
-    let queue = Vec::<Board>::new();
-    // skip...
-    Some(queue)
-}
+    if !is_solvable_to(&start, goal) {
+        return None;
+    }
+
+    let mut parents = HashMap::<Board, Board>::new();
+    let mut queue = VecDeque::from([start.clone()]);
+    parents.insert(start.clone(), start.clone());
 
-pub fn is_solvable(arr: &[u8]) -> bool {
-    let mut inversions = 0;
+    while let Some(board) = queue.pop_front() {
+        for next in board.neighbours() {
+            if parents.contains_key(&next) {
+                continue;
+            }
+            parents.insert(next.clone(), board.clone());
 
-    let len = arr.len();
-    for i in 0..len {
-        for j in i+1..len {
-            if arr[i] != 0 && arr[j] != 0 && arr[i] > arr[j] {
-                inversions += 1;
+            if next == *goal {
+                let mut path = vec![next];
+                let mut current = board;
+                while current != start {
+                    let parent = parents[&current].clone();
+                    path.push(current);
+                    current = parent;
+                }
+                path.reverse();
+                return Some(path);
             }
+            queue.push_back(next);
         }
     }
-    inversions % 2 == 0
+
+    None
+}
+
+/// Returns true if `board` can reach the final configuration.
+pub fn is_solvable(board: &Board) -> bool {
+    let (rows, cols) = board.size();
+    is_solvable_to(board, &Board::solved(rows, cols))
+}
+
+/// Returns true if `goal` can be reached from `start`. Boards of different sizes
+/// are never reachable from each other.
+///
+/// On boards with a single row or column tiles never change their order, so `goal`
+/// is reachable iff its tiles go in the same order as in `start`. Otherwise, every
+/// move is a transposition of the empty tile with an adjacent one, so the parity of
+/// the permutation taking `start` to `goal` must match the parity of the distance
+/// between their empty tiles. Every board satisfying this condition is reachable.
+pub fn is_solvable_to(start: &Board, goal: &Board) -> bool {
+    if start.size() != goal.size() {
+        return false;
+    }
+    let (from, to) = (start.values(), goal.values());
+
+    let (rows, cols) = start.size();
+    if rows == 1 || cols == 1 {
+        return from.iter().filter(|&&v| v != 0).eq(to.iter().filter(|&&v| v != 0));
+    }
+
+    // Cell of every tile in `goal`, indexed by tile values.
+    let mut target = vec![0; to.len()];
+    for (cell, &value) in to.iter().enumerate() {
+        target[value as usize] = cell;
+    }
+
+    // A permutation of n elements with c cycles is a product of n - c transpositions.
+    let mut visited = vec![false; from.len()];
+    let mut transpositions = 0;
+    for first in 0..from.len() {
+        if visited[first] {
+            continue;
+        }
+        let mut cell = first;
+        while !visited[cell] {
+            visited[cell] = true;
+            cell = target[from[cell] as usize];
+            transpositions += 1;
+        }
+        transpositions -= 1;
+    }
+
+    let ((r1, c1), (r2, c2)) = (start.empty_position(), goal.empty_position());
+    let distance = r1.abs_diff(r2) + c1.abs_diff(c2);
+    transpositions % 2 == distance % 2
 }
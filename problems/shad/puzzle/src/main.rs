@@ -1,17 +1,584 @@
 #![forbid(unsafe_code)]
 
-fn main() {
+use std::{
+    collections::BTreeMap,
+    env,
+    io::{self, BufRead, Read},
+    process,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use puzzle::{format_moves, Algorithm, Board, Heuristic, LinearConflict, PatternDatabase};
+use rand::{rngs::StdRng, SeedableRng};
+
+const USAGE: &str = "\
+Usage:
+    puzzle [--format board|moves|json] [--pdb FILE] < board
+    puzzle batch [--format board|moves|json] [--pdb FILE] [--threads N] < boards
+    puzzle pdb --size ROWSxCOLS [--pattern TILES]... [--group-size N] --output FILE
+    puzzle generate --size ROWSxCOLS [--count N] [--distance D] [--seed S]
+    puzzle grade [--pdb FILE] < board
+
+Options:
+    --format       how to print the solution (default: board)
+    --pdb          solve using a pattern database built by `puzzle pdb`
+    --threads      number of worker threads (default: number of CPUs)
+    --size         board size, e.g. 4x4
+    --pattern      comma-separated tiles of a single pattern, e.g. 1,2,3,4,5
+    --group-size   tiles per pattern if no --pattern is given (default: 5)
+    --output       file to write the pattern database to
+    --count        number of boards to generate (default: 1)
+    --distance     generate boards with an optimal solution of exactly D moves
+    --seed         seed of the random generator (default: random, printed to stderr)";
+
+/// How the solution is printed.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Every intermediate board, separated by `---`.
+    Board,
+    /// Compact move string describing how the empty tile moves, e.g. `DRUL`.
+    Moves,
+    /// JSON object with the move string and every intermediate board.
+    Json,
+}
+
+enum Command {
+    /// Solve a board read from stdin.
+    Solve { format: Format, pdb: Option<String> },
+    /// Solve every board of a stream of boards separated by empty lines.
+    Batch {
+        format: Format,
+        pdb: Option<String>,
+        threads: usize,
+    },
+    /// Build a pattern database and write it to `output`.
+    Pdb {
+        rows: usize,
+        cols: usize,
+        patterns: Vec<Vec<u8>>,
+        output: String,
+    },
+    /// Print `count` random solvable boards separated by empty lines.
+    Generate {
+        rows: usize,
+        cols: usize,
+        count: usize,
+        distance: Option<usize>,
+        seed: Option<u64>,
+    },
+    /// Print the optimal solution length and difficulty of a board read from stdin.
+    Grade { pdb: Option<String> },
+}
+
+/// Returns the value of option `name`, given either as `--name=value` or `--name value`.
+fn option_value(
+    name: &str,
+    inline: Option<&str>,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<String, String> {
+    match inline {
+        Some(value) => Ok(value.to_string()),
+        None => args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", name)),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: '{}'", name, value))
+}
+
+fn parse_args() -> Result<Command, String> {
+    let mut args = env::args().skip(1).peekable();
+    let subcommand = args.next_if(|arg| !arg.starts_with('-'));
+
+    let mut format = Format::Board;
+    let mut pdb = None;
+    let mut threads = None;
+    let mut size = None;
+    let mut patterns = Vec::new();
+    let mut group_size = 5;
+    let mut output = None;
+    let mut count = 1;
+    let mut distance = None;
+    let mut seed = None;
+    while let Some(arg) = args.next() {
+        let (name, inline) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        let value = option_value(name, inline, &mut args)?;
+        match (subcommand.as_deref(), name) {
+            (None | Some("batch"), "--format") => {
+                format = match value.as_str() {
+                    "board" => Format::Board,
+                    "moves" => Format::Moves,
+                    "json" => Format::Json,
+                    _ => return Err(format!("unknown format '{}'", value)),
+                }
+            }
+            (None | Some("batch" | "grade"), "--pdb") => pdb = Some(value),
+            (Some("batch"), "--threads") => threads = Some(parse_number(name, &value)?),
+            (Some("pdb" | "generate"), "--size") => {
+                let (rows, cols) = value
+                    .split_once('x')
+                    .ok_or_else(|| format!("invalid value for --size: '{}'", value))?;
+                size = Some((parse_number(name, rows)?, parse_number(name, cols)?));
+            }
+            (Some("pdb"), "--pattern") => patterns.push(
+                value
+                    .split(',')
+                    .map(|tile| parse_number(name, tile))
+                    .collect::<Result<_, _>>()?,
+            ),
+            (Some("pdb"), "--group-size") => group_size = parse_number(name, &value)?,
+            (Some("pdb"), "--output") => output = Some(value),
+            (Some("generate"), "--count") => count = parse_number(name, &value)?,
+            (Some("generate"), "--distance") => distance = Some(parse_number(name, &value)?),
+            (Some("generate"), "--seed") => seed = Some(parse_number(name, &value)?),
+            _ => return Err(format!("unexpected argument '{}'", arg)),
+        }
+    }
+
+    match subcommand.as_deref() {
+        None => Ok(Command::Solve { format, pdb }),
+        Some("batch") => {
+            let threads = threads
+                .or_else(|| thread::available_parallelism().ok().map(|n| n.get()))
+                .unwrap_or(1);
+            Ok(Command::Batch {
+                format,
+                pdb,
+                threads: threads.max(1),
+            })
+        }
+        Some("pdb") => {
+            let (rows, cols) = size.ok_or("missing --size")?;
+            if patterns.is_empty() {
+                patterns = PatternDatabase::default_patterns(rows, cols, group_size);
+            }
+            Ok(Command::Pdb {
+                rows,
+                cols,
+                patterns,
+                output: output.ok_or("missing --output")?,
+            })
+        }
+        Some("generate") => {
+            let (rows, cols) = size.ok_or("missing --size")?;
+            if rows == 0 || cols == 0 || rows * cols > 256 {
+                return Err(format!("invalid board size: {}x{}", rows, cols));
+            }
+            Ok(Command::Generate {
+                rows,
+                cols,
+                count,
+                distance,
+                seed,
+            })
+        }
+        Some("grade") => Ok(Command::Grade { pdb }),
+        Some(other) => Err(format!("unknown subcommand '{}'", other)),
+    }
+}
+
+fn board_to_json(board: &Board) -> String {
+    let (rows, cols) = board.size();
+    let values = board.values();
+    let rows = (0..rows)
+        .map(|r| {
+            let row = values[r * cols..(r + 1) * cols]
+                .iter()
+                .map(u8::to_string)
+                .collect::<Vec<_>>();
+            format!("[{}]", row.join(","))
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", rows.join(","))
+}
+
+fn print_solution(format: Format, start: &Board, solution: Option<Vec<Board>>) {
+    let path = match (format, solution) {
+        (Format::Json, None) => {
+            println!("{{\"solvable\":false}}");
+            return;
+        }
+        (_, None) => {
+            println!("No solution.");
+            return;
+        }
+        (_, Some(path)) => path,
+    };
+
+    let moves = start
+        .moves_along(&path)
+        .expect("solver returned a broken path");
+    match format {
+        Format::Board => {
+            for board in path {
+                print!("---\n{}", board);
+            }
+        }
+        Format::Moves => println!("{}", format_moves(&moves)),
+        Format::Json => {
+            let boards = path.iter().map(board_to_json).collect::<Vec<_>>();
+            println!(
+                "{{\"solvable\":true,\"length\":{},\"moves\":\"{}\",\"boards\":[{}]}}",
+                moves.len(),
+                format_moves(&moves),
+                boards.join(",")
+            );
+        }
+    }
+}
+
+fn build_pdb(rows: usize, cols: usize, patterns: &[Vec<u8>], output: &str) {
+    let pdb = PatternDatabase::build(rows, cols, patterns).unwrap_or_else(|err| {
+        eprintln!("puzzle: failed to build pattern database: {}", err);
+        process::exit(1);
+    });
+    if let Err(err) = pdb.save(output) {
+        eprintln!("puzzle: failed to write {}: {}", output, err);
+        process::exit(1);
+    }
+}
+
+/// Solves `board` with the fastest solver available for its size.
+fn solve_board(board: &Board, pdb: Option<&PatternDatabase>) -> Result<Option<Vec<Board>>, String> {
+    let (rows, cols) = board.size();
+    let solve_informed = |heuristic: &dyn Heuristic| {
+        puzzle::solve_with(board.clone(), heuristic, Algorithm::IdaStar).map(|s| s.moves)
+    };
+    match pdb {
+        Some(pdb) if pdb.size() != (rows, cols) => {
+            let (pdb_rows, pdb_cols) = pdb.size();
+            Err(format!(
+                "pattern database is built for {}x{} boards, got {}x{}",
+                pdb_rows, pdb_cols, rows, cols
+            ))
+        }
+        Some(pdb) => Ok(solve_informed(pdb)),
+        None if rows * cols <= 9 => Ok(puzzle::solve(board.clone())),
+        // Plain BFS does not scale past 3x3.
+        None => Ok(solve_informed(&LinearConflict)),
+    }
+}
+
+fn load_pdb(path: Option<String>) -> Option<PatternDatabase> {
+    path.map(|path| {
+        PatternDatabase::load(&path).unwrap_or_else(|err| {
+            eprintln!("puzzle: failed to read {}: {}", path, err);
+            process::exit(1);
+        })
+    })
+}
+
+fn read_board() -> Board {
     let mut input = String::new();
-    for _ in 0..3 {
-        std::io::stdin().read_line(&mut input).unwrap();
+    if let Err(err) = io::stdin().read_to_string(&mut input) {
+        eprintln!("puzzle: failed to read stdin: {}", err);
+        process::exit(1);
     }
 
-    let board = puzzle::Board::from_string(&input);
-    if let Some(moves) = puzzle::solve(board) {
-        for mv in moves {
-            print!("---\n{}", mv.to_string());
+    input.parse::<Board>().unwrap_or_else(|err| {
+        eprintln!("puzzle: invalid board: {}", err);
+        process::exit(1);
+    })
+}
+
+fn run_single(format: Format, pdb: Option<String>) {
+    let pdb = load_pdb(pdb);
+    let board = read_board();
+    match solve_board(&board, pdb.as_ref()) {
+        Ok(solution) => print_solution(format, &board, solution),
+        Err(err) => {
+            eprintln!("puzzle: {}", err);
+            process::exit(1);
         }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// A board of the batch input, as it appears in the stream.
+struct Job {
+    index: usize,
+    /// Line of the input where the board starts, counted from 1.
+    line: usize,
+    text: String,
+}
+
+enum Outcome {
+    Solved(Board, Vec<Board>),
+    Unsolvable,
+    Malformed(String),
+}
+
+/// Splits `input` into boards separated by empty lines and sends them to `jobs`.
+fn read_jobs(input: impl BufRead, jobs: mpsc::SyncSender<Job>) -> io::Result<()> {
+    let mut job: Option<Job> = None;
+    let mut index = 0;
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            if let Some(job) = job.take() {
+                if jobs.send(job).is_err() {
+                    return Ok(());
+                }
+            }
+            continue;
+        }
+
+        let job = job.get_or_insert_with(|| {
+            index += 1;
+            Job {
+                index,
+                line: i + 1,
+                text: String::new(),
+            }
+        });
+        job.text.push_str(&line);
+        job.text.push('\n');
+    }
+    if let Some(job) = job {
+        let _ = jobs.send(job);
+    }
+    Ok(())
+}
+
+fn run_job(job: &Job, pdb: Option<&PatternDatabase>) -> (Outcome, Duration) {
+    let board = match job.text.parse::<Board>() {
+        Ok(board) => board,
+        Err(err) => {
+            // Report lines of the whole stream rather than of the board.
+            let message = err.offset_lines(job.line - 1).to_string();
+            return (Outcome::Malformed(message), Duration::ZERO);
+        }
+    };
+
+    let start = Instant::now();
+    let outcome = match solve_board(&board, pdb) {
+        Ok(Some(path)) => Outcome::Solved(board, path),
+        Ok(None) => Outcome::Unsolvable,
+        Err(err) => Outcome::Malformed(err),
+    };
+    (outcome, start.elapsed())
+}
+
+fn json_escape(s: &str) -> String {
+    s.chars()
+        .flat_map(|chr| match chr {
+            '"' | '\\' => vec!['\\', chr],
+            _ => vec![chr],
+        })
+        .collect()
+}
+
+fn print_outcome(format: Format, index: usize, outcome: &Outcome, time: Duration) {
+    let millis = time.as_secs_f64() * 1000.0;
+    match (format, outcome) {
+        (Format::Json, Outcome::Solved(start, path)) => {
+            let moves = start
+                .moves_along(path)
+                .expect("solver returned a broken path");
+            println!(
+                "{{\"index\":{},\"status\":\"solved\",\"length\":{},\"time_ms\":{:.3},\"moves\":\"{}\"}}",
+                index,
+                moves.len(),
+                millis,
+                format_moves(&moves)
+            );
+        }
+        (Format::Json, Outcome::Unsolvable) => println!(
+            "{{\"index\":{},\"status\":\"unsolvable\",\"time_ms\":{:.3}}}",
+            index, millis
+        ),
+        (Format::Json, Outcome::Malformed(err)) => println!(
+            "{{\"index\":{},\"status\":\"malformed\",\"error\":\"{}\"}}",
+            index,
+            json_escape(err)
+        ),
+        (_, Outcome::Solved(start, path)) => {
+            print!(
+                "#{} solved length={} time={:.3}ms",
+                index,
+                path.len(),
+                millis
+            );
+            if format == Format::Moves {
+                let moves = start
+                    .moves_along(path)
+                    .expect("solver returned a broken path");
+                println!(" moves={}", format_moves(&moves));
+            } else {
+                println!();
+                for board in path {
+                    print!("---\n{}", board);
+                }
+            }
+        }
+        (_, Outcome::Unsolvable) => println!("#{} unsolvable time={:.3}ms", index, millis),
+        (_, Outcome::Malformed(err)) => println!("#{} malformed: {}", index, err),
+    }
+}
+
+/// Solves boards read from stdin on `threads` workers and prints results in input order.
+/// Returns `false` if some boards are malformed.
+fn run_batch(format: Format, pdb: Option<String>, threads: usize) -> bool {
+    let pdb = load_pdb(pdb);
+    let (job_sender, job_receiver) = mpsc::sync_channel::<Job>(threads * 2);
+    let (result_sender, result_receiver) = mpsc::channel();
+    let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+    let (mut solved, mut unsolvable, mut malformed) = (0, 0, 0);
+    thread::scope(|scope| {
+        let reader = scope.spawn(|| read_jobs(io::stdin().lock(), job_sender));
+
+        for _ in 0..threads {
+            let jobs = Arc::clone(&job_receiver);
+            let results = result_sender.clone();
+            let pdb = pdb.as_ref();
+            scope.spawn(move || loop {
+                let job = match jobs.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => return,
+                };
+                let (outcome, time) = run_job(&job, pdb);
+                if results.send((job.index, outcome, time)).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(result_sender);
+
+        // Results arrive in any order, so keep them until all previous ones are printed.
+        let mut pending = BTreeMap::new();
+        let mut next = 1;
+        for (index, outcome, time) in result_receiver {
+            pending.insert(index, (outcome, time));
+            while let Some((outcome, time)) = pending.remove(&next) {
+                match outcome {
+                    Outcome::Solved(..) => solved += 1,
+                    Outcome::Unsolvable => unsolvable += 1,
+                    Outcome::Malformed(_) => malformed += 1,
+                }
+                print_outcome(format, next, &outcome, time);
+                next += 1;
+            }
+        }
+
+        if let Err(err) = reader.join().unwrap() {
+            eprintln!("puzzle: failed to read stdin: {}", err);
+            process::exit(1);
+        }
+    });
+
+    if format == Format::Json {
+        println!(
+            "{{\"summary\":{{\"solved\":{},\"unsolvable\":{},\"malformed\":{}}}}}",
+            solved, unsolvable, malformed
+        );
     } else {
-        println!("No solution.");
+        println!(
+            "solved: {}, unsolvable: {}, malformed: {}",
+            solved, unsolvable, malformed
+        );
+    }
+    malformed == 0
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+fn run_generate(
+    rows: usize,
+    cols: usize,
+    count: usize,
+    distance: Option<usize>,
+    seed: Option<u64>,
+) {
+    let seed = seed.unwrap_or_else(|| {
+        let seed = rand::random();
+        eprintln!("seed: {}", seed);
+        seed
+    });
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    for i in 0..count {
+        let board = match distance {
+            Some(distance) => puzzle::board_at_distance(rows, cols, distance, &mut rng)
+                .unwrap_or_else(|| {
+                    eprintln!(
+                        "puzzle: failed to find a {}x{} board at distance {}",
+                        rows, cols, distance
+                    );
+                    process::exit(1);
+                }),
+            None => puzzle::random_board(rows, cols, &mut rng),
+        };
+        if i > 0 {
+            println!();
+        }
+        print!("{}", board);
+    }
+}
+
+fn run_grade(pdb: Option<String>) {
+    let pdb = load_pdb(pdb);
+    let board = read_board();
+    let heuristic: &dyn Heuristic = match pdb.as_ref() {
+        Some(pdb) if pdb.size() != board.size() => {
+            eprintln!("puzzle: pattern database is built for another board size");
+            process::exit(1);
+        }
+        Some(pdb) => pdb,
+        None => &LinearConflict,
+    };
+
+    match puzzle::grade(&board, heuristic) {
+        Some(grade) => println!(
+            "length={} difficulty={}",
+            grade.length,
+            grade.difficulty.map_or("unknown", |d| d.name())
+        ),
+        None => println!("No solution."),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+fn main() {
+    let command = parse_args().unwrap_or_else(|err| {
+        eprintln!("puzzle: {}\n{}", err, USAGE);
+        process::exit(2);
+    });
+    match command {
+        Command::Solve { format, pdb } => run_single(format, pdb),
+        Command::Batch {
+            format,
+            pdb,
+            threads,
+        } => {
+            if !run_batch(format, pdb, threads) {
+                process::exit(1);
+            }
+        }
+        Command::Pdb {
+            rows,
+            cols,
+            patterns,
+            output,
+        } => build_pdb(rows, cols, &patterns, &output),
+        Command::Generate {
+            rows,
+            cols,
+            count,
+            distance,
+            seed,
+        } => run_generate(rows, cols, count, distance, seed),
+        Command::Grade { pdb } => run_grade(pdb),
     }
 }
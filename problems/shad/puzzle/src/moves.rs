@@ -0,0 +1,135 @@
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::Board;
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Direction in which the empty tile moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Move {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+impl Move {
+    /// All moves in the order in which `Board::neighbours` generates them.
+    pub const ALL: [Move; 4] = [Move::Up, Move::Down, Move::Left, Move::Right];
+
+    /// Returns the move that undoes this one.
+    pub fn inverse(self) -> Self {
+        match self {
+            Move::Up => Move::Down,
+            Move::Down => Move::Up,
+            Move::Left => Move::Right,
+            Move::Right => Move::Left,
+        }
+    }
+
+    /// Returns the letter denoting this move: `U`, `D`, `L` or `R`.
+    pub fn letter(self) -> char {
+        match self {
+            Move::Up => 'U',
+            Move::Down => 'D',
+            Move::Left => 'L',
+            Move::Right => 'R',
+        }
+    }
+
+    /// Returns the move denoted by `letter`, or `None` if there is no such move.
+    pub fn from_letter(letter: char) -> Option<Self> {
+        match letter {
+            'U' => Some(Move::Up),
+            'D' => Some(Move::Down),
+            'L' => Some(Move::Left),
+            'R' => Some(Move::Right),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.letter())
+    }
+}
+
+/// Formats `moves` as a compact move string, e.g. `"DRUL"`.
+pub fn format_moves(moves: &[Move]) -> String {
+    moves.iter().map(|mv| mv.letter()).collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Error returned when a move string cannot be replayed onto a `Board`.
+///
+/// Positions are counted from 1 and refer to characters of the move string.
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    #[error("position {position}: unknown move {chr:?}")]
+    UnknownMove { position: usize, chr: char },
+    #[error("position {position}: move {mv} leaves the board")]
+    IllegalMove { position: usize, mv: Move },
+}
+
+impl Board {
+    /// Returns the board obtained by moving the empty tile in the direction of `mv`,
+    /// or `None` if the empty tile would leave the board.
+    pub fn apply(&self, mv: Move) -> Option<Board> {
+        let (rows, cols) = self.size();
+        let (r, c) = self.empty_position();
+        let (nr, nc) = match mv {
+            Move::Up => (r.wrapping_sub(1), c),
+            Move::Down => (r + 1, c),
+            Move::Left => (r, c.wrapping_sub(1)),
+            Move::Right => (r, c + 1),
+        };
+        if nr >= rows || nc >= cols {
+            return None;
+        }
+
+        let mut board = self.clone();
+        board.swap(r, c, nr, nc);
+        Some(board)
+    }
+
+    /// Applies every move of the string `moves` (e.g. `"DRUL"`) in order and returns
+    /// the final board. Whitespace in `moves` is ignored.
+    pub fn replay(&self, moves: &str) -> Result<Board, ReplayError> {
+        let mut board = self.clone();
+        for (i, chr) in moves.chars().enumerate() {
+            if chr.is_whitespace() {
+                continue;
+            }
+            let position = i + 1;
+            let mv = Move::from_letter(chr).ok_or(ReplayError::UnknownMove { position, chr })?;
+            board = board
+                .apply(mv)
+                .ok_or(ReplayError::IllegalMove { position, mv })?;
+        }
+        Ok(board)
+    }
+
+    /// Returns the move that turns this board into `next`, or `None` if `next`
+    /// is not one of `self.neighbours()`.
+    pub fn move_to(&self, next: &Board) -> Option<Move> {
+        Move::ALL
+            .into_iter()
+            .find(|&mv| self.apply(mv).as_ref() == Some(next))
+    }
+
+    /// Converts a sequence of boards, as returned by `solve`, into moves starting
+    /// from this board. Returns `None` if some consecutive boards are not adjacent.
+    pub fn moves_along(&self, path: &[Board]) -> Option<Vec<Move>> {
+        let mut current = self;
+        let mut moves = Vec::with_capacity(path.len());
+        for next in path {
+            moves.push(current.move_to(next)?);
+            current = next;
+        }
+        Some(moves)
+    }
+}
@@ -0,0 +1,370 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use thiserror::Error;
+
+use crate::{heuristic::Heuristic, Board};
+
+////////////////////////////////////////////////////////////////////////////////
+
+const MAGIC: &[u8; 6] = b"PZPDB\0";
+const VERSION: u8 = 1;
+
+/// Maximal number of tiles in a single pattern.
+pub const MAX_PATTERN_TILES: usize = 8;
+
+/// Maximal number of entries in the search table of a single pattern, one byte
+/// each, i.e. `rows * cols` times the number of placements of its tiles.
+pub const MAX_TABLE_SIZE: usize = 1 << 28;
+
+/// Error returned when a pattern database cannot be built, read or written.
+#[derive(Error, Debug)]
+pub enum PdbError {
+    #[error("invalid board size: {rows}x{cols}")]
+    InvalidSize { rows: usize, cols: usize },
+    #[error("pattern is empty")]
+    EmptyPattern,
+    #[error("pattern has {0} tiles, at most {MAX_PATTERN_TILES} are supported")]
+    PatternTooLarge(usize),
+    #[error("pattern of {0} tiles needs more than {MAX_TABLE_SIZE} table entries")]
+    TableTooLarge(usize),
+    #[error("tile {0} does not fit on the board")]
+    TileOutOfRange(u8),
+    #[error("tile {0} belongs to more than one pattern")]
+    DuplicateTile(u8),
+    #[error("not a pattern database file")]
+    BadMagic,
+    #[error("unsupported pattern database version {0}")]
+    UnsupportedVersion(u8),
+    #[error("pattern database file is corrupted")]
+    Corrupted,
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Number of ways to place `k` distinct tiles on `n` cells.
+fn placements(n: usize, k: usize) -> usize {
+    (n - k + 1..=n).product()
+}
+
+/// Number of entries in the search table of a pattern of `k` tiles, `None` if
+/// it does not fit into `usize`.
+fn table_size(n: usize, k: usize) -> Option<usize> {
+    (n - k + 1..=n).try_fold(n, |size, cells| size.checked_mul(cells))
+}
+
+/// Maps positions of pattern tiles onto `0..placements(n, k)`.
+fn rank(positions: &[u8], n: usize) -> usize {
+    let k = positions.len();
+    let mut index = 0;
+    for (i, &p) in positions.iter().enumerate() {
+        let smaller_used = positions[..i].iter().filter(|&&q| q < p).count();
+        index += (p as usize - smaller_used) * placements(n - i - 1, k - i - 1);
+    }
+    index
+}
+
+/// Inverse of `rank`.
+fn unrank(mut index: usize, n: usize, positions: &mut [u8]) {
+    let k = positions.len();
+    let mut used = vec![false; n];
+    for (i, position) in positions.iter_mut().enumerate() {
+        let weight = placements(n - i - 1, k - i - 1);
+        let skip = index / weight;
+        index %= weight;
+        let cell = (0..n)
+            .filter(|&cell| !used[cell])
+            .nth(skip)
+            .expect("index is out of range");
+        used[cell] = true;
+        *position = cell as u8;
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Costs of moving a group of tiles to their final positions.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Pattern {
+    tiles: Vec<u8>,
+    /// Minimal number of moves of pattern tiles, indexed by `rank` of their positions.
+    costs: Vec<u8>,
+}
+
+impl Pattern {
+    /// Runs a 0-1 breadth-first search from the final configuration, where only
+    /// moves of pattern tiles cost one move and other tiles are indistinguishable.
+    fn build(rows: usize, cols: usize, tiles: Vec<u8>) -> Self {
+        let n = rows * cols;
+        let k = tiles.len();
+        let states = placements(n, k);
+
+        // Distance for every (placement of pattern tiles, empty cell) pair.
+        let mut distances = vec![u8::MAX; states * n];
+        let mut queue = VecDeque::new();
+        let goal = tiles.iter().map(|&t| t - 1).collect::<Vec<_>>();
+        let start = rank(&goal, n) * n + (n - 1);
+        distances[start] = 0;
+        queue.push_back(start);
+
+        let mut positions = vec![0; k];
+        while let Some(state) = queue.pop_front() {
+            let distance = distances[state];
+            let (index, empty) = (state / n, state % n);
+            unrank(index, n, &mut positions);
+
+            let (r, c) = (empty / cols, empty % cols);
+            let adjacent = [
+                (r.wrapping_sub(1), c),
+                (r + 1, c),
+                (r, c.wrapping_sub(1)),
+                (r, c + 1),
+            ];
+            for (nr, nc) in adjacent {
+                if nr >= rows || nc >= cols {
+                    continue;
+                }
+                let cell = nr * cols + nc;
+                let (next, cost) = match positions.iter().position(|&p| p as usize == cell) {
+                    Some(j) => {
+                        positions[j] = empty as u8;
+                        let next = rank(&positions, n) * n + cell;
+                        positions[j] = cell as u8;
+                        (next, 1)
+                    }
+                    None => (index * n + cell, 0),
+                };
+
+                let next_distance = distance.saturating_add(cost);
+                if next_distance < distances[next] {
+                    distances[next] = next_distance;
+                    if cost == 0 {
+                        queue.push_front(next);
+                    } else {
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        let costs = distances
+            .chunks(n)
+            .map(|chunk| chunk.iter().copied().min().unwrap_or(u8::MAX))
+            .collect();
+        Self { tiles, costs }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Additive pattern database for boards of a fixed size.
+///
+/// Tiles are split into disjoint patterns. For every pattern the database stores
+/// the exact number of moves of its tiles required to put them on their final
+/// positions. Since every move belongs to exactly one pattern, the sum of these
+/// costs never overestimates the distance to the final configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatternDatabase {
+    rows: usize,
+    cols: usize,
+    patterns: Vec<Pattern>,
+}
+
+impl PatternDatabase {
+    /// Precomputes a database for `rows` x `cols` boards with the given disjoint
+    /// groups of tiles. Tiles not covered by any pattern are ignored.
+    ///
+    /// Building takes time and memory proportional to `rows * cols` times the number
+    /// of placements of the largest pattern, so patterns of 5-6 tiles are practical.
+    /// Patterns whose table exceeds `MAX_TABLE_SIZE` entries are rejected.
+    pub fn build(rows: usize, cols: usize, patterns: &[Vec<u8>]) -> Result<Self, PdbError> {
+        let n = rows * cols;
+        if rows == 0 || cols == 0 || n > 256 {
+            return Err(PdbError::InvalidSize { rows, cols });
+        }
+
+        let mut seen = vec![false; n];
+        for pattern in patterns {
+            if pattern.is_empty() {
+                return Err(PdbError::EmptyPattern);
+            }
+            if pattern.len() > MAX_PATTERN_TILES {
+                return Err(PdbError::PatternTooLarge(pattern.len()));
+            }
+            for &tile in pattern {
+                if tile == 0 || tile as usize >= n {
+                    return Err(PdbError::TileOutOfRange(tile));
+                }
+                if seen[tile as usize] {
+                    return Err(PdbError::DuplicateTile(tile));
+                }
+                seen[tile as usize] = true;
+            }
+            // Distinct tiles within the board leave at least the blank cell free.
+            if table_size(n, pattern.len()).is_none_or(|size| size > MAX_TABLE_SIZE) {
+                return Err(PdbError::TableTooLarge(pattern.len()));
+            }
+        }
+
+        let patterns = patterns
+            .iter()
+            .map(|tiles| Pattern::build(rows, cols, tiles.clone()))
+            .collect();
+        Ok(Self {
+            rows,
+            cols,
+            patterns,
+        })
+    }
+
+    /// Splits tiles into consecutive groups of at most `group_size` tiles,
+    /// e.g. 1-5, 6-10 and 11-15 for a 4x4 board and `group_size` 5.
+    pub fn default_patterns(rows: usize, cols: usize, group_size: usize) -> Vec<Vec<u8>> {
+        let tiles = (1..rows * cols).map(|n| n as u8).collect::<Vec<_>>();
+        tiles
+            .chunks(group_size.max(1))
+            .map(|chunk| chunk.to_vec())
+            .collect()
+    }
+
+    /// Returns `(rows, cols)` of boards this database is built for.
+    pub fn size(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    /// Returns tiles of every pattern.
+    pub fn patterns(&self) -> Vec<Vec<u8>> {
+        self.patterns.iter().map(|p| p.tiles.clone()).collect()
+    }
+
+    /// Writes this database in a compact binary format: a header followed by
+    /// the list of tiles and one byte per placement for every pattern.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), PdbError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[
+            VERSION,
+            (self.rows - 1) as u8,
+            (self.cols - 1) as u8,
+            self.patterns.len() as u8,
+        ])?;
+        for pattern in self.patterns.iter() {
+            writer.write_all(&[pattern.tiles.len() as u8])?;
+            writer.write_all(&pattern.tiles)?;
+            writer.write_all(&pattern.costs)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a database written by `write_to`.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, PdbError> {
+        fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), PdbError> {
+            reader.read_exact(buf).map_err(|err| match err.kind() {
+                io::ErrorKind::UnexpectedEof => PdbError::Corrupted,
+                _ => PdbError::Io(err),
+            })
+        }
+
+        let mut magic = [0; 6];
+        read_exact(&mut reader, &mut magic)?;
+        if &magic != MAGIC {
+            return Err(PdbError::BadMagic);
+        }
+        let mut header = [0; 4];
+        read_exact(&mut reader, &mut header)?;
+        let [version, rows, cols, count] = header;
+        if version != VERSION {
+            return Err(PdbError::UnsupportedVersion(version));
+        }
+        let (rows, cols) = (rows as usize + 1, cols as usize + 1);
+        let n = rows * cols;
+        if n > 256 {
+            return Err(PdbError::Corrupted);
+        }
+
+        let mut patterns = Vec::with_capacity(count as usize);
+        let mut seen = vec![false; n];
+        for _ in 0..count {
+            let mut len = [0];
+            read_exact(&mut reader, &mut len)?;
+            let len = len[0] as usize;
+            if len == 0 || len > MAX_PATTERN_TILES {
+                return Err(PdbError::Corrupted);
+            }
+
+            let mut tiles = vec![0; len];
+            read_exact(&mut reader, &mut tiles)?;
+            for &tile in tiles.iter() {
+                if tile == 0 || tile as usize >= n || seen[tile as usize] {
+                    return Err(PdbError::Corrupted);
+                }
+                seen[tile as usize] = true;
+            }
+            // A header of a database that `build` would reject cannot be trusted
+            // with an allocation.
+            if table_size(n, len).is_none_or(|size| size > MAX_TABLE_SIZE) {
+                return Err(PdbError::Corrupted);
+            }
+
+            let mut costs = vec![0; placements(n, len)];
+            read_exact(&mut reader, &mut costs)?;
+            patterns.push(Pattern { tiles, costs });
+        }
+
+        if reader.read(&mut [0])? != 0 {
+            return Err(PdbError::Corrupted);
+        }
+        Ok(Self {
+            rows,
+            cols,
+            patterns,
+        })
+    }
+
+    /// Writes this database to the file at `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PdbError> {
+        self.write_to(BufWriter::new(File::create(path)?))
+    }
+
+    /// Reads a database from the file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, PdbError> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+}
+
+/// # Panics
+///
+/// Panics if `board` has a different size than this database.
+impl Heuristic for PatternDatabase {
+    fn estimate(&self, board: &Board) -> u32 {
+        assert_eq!(
+            board.size(),
+            (self.rows, self.cols),
+            "pattern database is built for another board size"
+        );
+
+        let n = self.rows * self.cols;
+        let mut cells = vec![0; n];
+        for (cell, value) in board.values().into_iter().enumerate() {
+            cells[value as usize] = cell as u8;
+        }
+
+        let mut positions = [0; MAX_PATTERN_TILES];
+        self.patterns
+            .iter()
+            .map(|pattern| {
+                let positions = &mut positions[..pattern.tiles.len()];
+                for (position, &tile) in positions.iter_mut().zip(pattern.tiles.iter()) {
+                    *position = cells[tile as usize];
+                }
+                pattern.costs[rank(positions, n)] as u32
+            })
+            .sum()
+    }
+}
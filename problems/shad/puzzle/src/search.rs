@@ -0,0 +1,167 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+};
+
+use crate::{heuristic::Heuristic, is_solvable, Board};
+
+////////////////////////////////////////////////////////////////////////////////
+
+/// Informed search algorithm used by `solve_with`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Algorithm {
+    /// A* search. Keeps every visited board in memory, expands each board at most once.
+    AStar,
+    /// Iterative deepening A*. Uses memory proportional to the solution length,
+    /// but may expand the same board many times.
+    IdaStar,
+}
+
+/// Result of an informed search.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Solution {
+    /// Sequence of boards in the same format as returned by `solve`.
+    pub moves: Vec<Board>,
+    /// Number of boards whose neighbours were generated during the search.
+    pub nodes_expanded: u64,
+}
+
+/// Returns the shortest sequence of moves that solves this board, found by
+/// the given informed search `algorithm` guided by `heuristic`.
+///
+/// The returned path is optimal as long as `heuristic` is admissible.
+/// If the board is unsolvable, returns `None`.
+pub fn solve_with<H: Heuristic + ?Sized>(
+    start: Board,
+    heuristic: &H,
+    algorithm: Algorithm,
+) -> Option<Solution> {
+    if !is_solvable(&start) {
+        return None;
+    }
+
+    match algorithm {
+        Algorithm::AStar => a_star(start, heuristic),
+        Algorithm::IdaStar => ida_star(start, heuristic),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+fn a_star<H: Heuristic + ?Sized>(start: Board, heuristic: &H) -> Option<Solution> {
+    // Visited boards together with their parent index and best known distance.
+    let estimate = heuristic.estimate(&start);
+    let mut best = HashMap::<Board, usize>::from([(start.clone(), 0)]);
+    let mut nodes: Vec<(Board, usize, u32)> = vec![(start, 0, 0)];
+    // Ordered by the smallest estimate, then by the deepest board.
+    let mut open = BinaryHeap::from([(Reverse(estimate), 0, 0)]);
+    let mut nodes_expanded = 0;
+
+    while let Some((_, _, index)) = open.pop() {
+        let (board, _, distance) = nodes[index].clone();
+        if best[&board] != index {
+            // A shorter path to this board was found after it had been queued.
+            continue;
+        }
+
+        if board.is_solved() {
+            let mut moves = Vec::new();
+            let mut current = index;
+            while current != 0 {
+                moves.push(nodes[current].0.clone());
+                current = nodes[current].1;
+            }
+            moves.reverse();
+            return Some(Solution {
+                moves,
+                nodes_expanded,
+            });
+        }
+
+        nodes_expanded += 1;
+        for next in board.neighbours() {
+            let next_distance = distance + 1;
+            match best.get(&next) {
+                Some(&known) if nodes[known].2 <= next_distance => continue,
+                _ => {}
+            }
+
+            let estimate = next_distance + heuristic.estimate(&next);
+            best.insert(next.clone(), nodes.len());
+            nodes.push((next, index, next_distance));
+            open.push((Reverse(estimate), next_distance, nodes.len() - 1));
+        }
+    }
+
+    None
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+enum Bound {
+    Found,
+    Exceeded(u32),
+    Exhausted,
+}
+
+fn ida_star<H: Heuristic + ?Sized>(start: Board, heuristic: &H) -> Option<Solution> {
+    let mut threshold = heuristic.estimate(&start);
+    let mut path = vec![start];
+    let mut nodes_expanded = 0;
+
+    loop {
+        match ida_search(&mut path, 0, threshold, heuristic, &mut nodes_expanded) {
+            Bound::Found => {
+                path.remove(0);
+                return Some(Solution {
+                    moves: path,
+                    nodes_expanded,
+                });
+            }
+            Bound::Exceeded(next) => threshold = next,
+            Bound::Exhausted => return None,
+        }
+    }
+}
+
+fn ida_search<H: Heuristic + ?Sized>(
+    path: &mut Vec<Board>,
+    distance: u32,
+    threshold: u32,
+    heuristic: &H,
+    nodes_expanded: &mut u64,
+) -> Bound {
+    let board = path.last().unwrap();
+    let estimate = distance + heuristic.estimate(board);
+    if estimate > threshold {
+        return Bound::Exceeded(estimate);
+    }
+    if board.is_solved() {
+        return Bound::Found;
+    }
+
+    *nodes_expanded += 1;
+    let parent = path.len().checked_sub(2).map(|i| &path[i]);
+    let children = board
+        .neighbours()
+        .into_iter()
+        .filter(|next| Some(next) != parent)
+        .collect::<Vec<_>>();
+    let mut minimum = None;
+    for next in children {
+        path.push(next);
+        match ida_search(path, distance + 1, threshold, heuristic, nodes_expanded) {
+            Bound::Found => return Bound::Found,
+            Bound::Exceeded(bound) => {
+                minimum = Some(minimum.map_or(bound, |m: u32| m.min(bound)));
+            }
+            Bound::Exhausted => {}
+        }
+        path.pop();
+    }
+
+    match minimum {
+        Some(bound) => Bound::Exceeded(bound),
+        None => Bound::Exhausted,
+    }
+}
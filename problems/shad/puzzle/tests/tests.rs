@@ -1,4 +1,23 @@
-use puzzle::{solve, Board, Tile};
+use puzzle::{
+    board_at_distance, format_moves, grade, is_solvable, is_solvable_to, random_board, solve,
+    solve_to, solve_with, Algorithm, Board, Difficulty, Grade, Heuristic, LinearConflict,
+    Manhattan, MisplacedTiles, Move, ParseBoardError, PatternDatabase, PdbError, ReplayError, Tile,
+};
+use rand::{rngs::StdRng, SeedableRng};
+
+fn assert_valid_path(start: &Board, moves: &[Board]) {
+    let mut current = start;
+    for next in moves {
+        assert!(
+            current.neighbours().contains(next),
+            "illegal move:\n{}---\n{}",
+            current,
+            next
+        );
+        current = next;
+    }
+    assert!(current.is_solved());
+}
 
 #[test]
 fn test_tile_basics() {
@@ -16,10 +35,20 @@ fn test_tile_0() {
     Tile::new(Some(0));
 }
 
+#[test]
+fn test_tile_large() {
+    assert_eq!(Tile::new(Some(9)).number(), Some(9));
+    assert_eq!(Tile::new(Some(255)).number(), Some(255));
+}
+
 #[test]
 #[should_panic]
-fn test_tile_9() {
-    Tile::new(Some(9));
+fn test_board_tile_out_of_range() {
+    Board::new([
+        [Tile::new(Some(1)), Tile::new(Some(2)), Tile::new(Some(3))],
+        [Tile::new(Some(4)), Tile::new(Some(5)), Tile::new(Some(6))],
+        [Tile::new(Some(7)), Tile::new(Some(9)), Tile::empty()],
+    ]);
 }
 
 #[test]
@@ -84,7 +113,7 @@ fn test_board_illegal_from_string_2() {
 }
 
 
-#[test] // skipped
+#[test]
 fn test_solve() {
     assert_eq!(
         solve(Board::from_string(
@@ -142,3 +171,712 @@ fn test_solve() {
     assert!(solution.is_some());
     assert_eq!(solution.unwrap().len(), 24);
 }
+
+#[test]
+fn test_heuristics() {
+    let solved = Board::from_string(
+        "123\n\
+         456\n\
+         78.\n",
+    );
+    assert_eq!(MisplacedTiles.estimate(&solved), 0);
+    assert_eq!(Manhattan.estimate(&solved), 0);
+    assert_eq!(LinearConflict.estimate(&solved), 0);
+
+    let board = Board::from_string(
+        "321\n\
+         654\n\
+         .78\n",
+    );
+    assert_eq!(MisplacedTiles.estimate(&board), 6);
+    assert_eq!(Manhattan.estimate(&board), 10);
+    assert_eq!(LinearConflict.estimate(&board), 18);
+}
+
+#[test]
+fn test_solve_with() {
+    let heuristics: [&dyn Heuristic; 3] = [&MisplacedTiles, &Manhattan, &LinearConflict];
+    let board = Board::from_string(
+        "321\n\
+         654\n\
+         .78\n",
+    );
+
+    for heuristic in heuristics {
+        for algorithm in [Algorithm::AStar, Algorithm::IdaStar] {
+            let solution = solve_with(board.clone(), heuristic, algorithm).unwrap();
+            assert_eq!(solution.moves.len(), 24);
+            assert_valid_path(&board, &solution.moves);
+
+            let solved = solve_with(solution.moves[23].clone(), heuristic, algorithm).unwrap();
+            assert!(solved.moves.is_empty());
+
+            let unsolvable = Board::from_string(
+                "123\n\
+                 456\n\
+                 87.\n",
+            );
+            assert_eq!(solve_with(unsolvable, heuristic, algorithm), None);
+        }
+    }
+
+    let expanded = |heuristic: &dyn Heuristic| {
+        solve_with(board.clone(), heuristic, Algorithm::AStar)
+            .unwrap()
+            .nodes_expanded
+    };
+    assert!(expanded(&Manhattan) < expanded(&MisplacedTiles));
+    assert!(expanded(&LinearConflict) <= expanded(&Manhattan));
+}
+
+#[test]
+fn test_solve_with_matches_bfs() {
+    for input in ["12.\n453\n786\n", "8.6\n547\n231\n", "647\n85.\n321\n"] {
+        let board = Board::from_string(input);
+        let expected = solve(board.clone()).unwrap();
+        for algorithm in [Algorithm::AStar, Algorithm::IdaStar] {
+            let solution = solve_with(board.clone(), &LinearConflict, algorithm).unwrap();
+            assert_eq!(solution.moves.len(), expected.len());
+            assert_valid_path(&board, &solution.moves);
+        }
+    }
+}
+
+#[test]
+fn test_board_sizes() {
+    let board = Board::from_string(
+        " 1  2  3  4\n\
+          5  6  .  8\n\
+          9 10  7 11\n\
+         13 14 15 12\n",
+    );
+    assert_eq!(board.size(), (4, 4));
+    assert_eq!(board.get(2, 1), Tile::new(Some(10)));
+    assert_eq!(board.empty_position(), (1, 2));
+    assert_eq!(Board::from_string(&board.to_string()), board);
+    assert_eq!(
+        board.to_string(),
+        " 1  2  3  4\n\
+         \x205  6  .  8\n\
+         \x209 10  7 11\n\
+         13 14 15 12\n",
+    );
+
+    let rectangular = Board::from_string(
+        "123\n\
+         4.5\n",
+    );
+    assert_eq!(rectangular.size(), (2, 3));
+    assert_eq!(rectangular.to_string(), "123\n4.5\n");
+    assert!(Board::solved(2, 3).is_solved());
+    assert!(Board::solved(5, 5).is_solved());
+    assert!(!rectangular.is_solved());
+}
+
+#[test]
+#[should_panic]
+fn test_board_illegal_from_string_ragged() {
+    Board::from_string(
+        "123\n\
+         45\n\
+         786.\n",
+    );
+}
+
+#[test]
+fn test_is_solvable_even_width() {
+    // Odd number of inversions, but the empty tile is one row above the bottom one.
+    assert!(is_solvable(&Board::from_string(
+        " 1  2  3  4\n\
+          5  6  7  8\n\
+          9 10 11  .\n\
+         13 14 15 12\n"
+    )));
+    assert!(!is_solvable(&Board::from_string(
+        " 1  2  3  4\n\
+          5  6  7  8\n\
+          9 10 11 12\n\
+         13 15 14  .\n"
+    )));
+    assert!(!is_solvable(&Board::from_string(
+        "13\n\
+         .2\n"
+    )));
+    assert!(is_solvable(&Board::from_string(
+        "23\n\
+         .1\n"
+    )));
+}
+
+#[test]
+fn test_is_solvable_single_line() {
+    // Tiles of a single row or column never change their order, whatever the parity.
+    assert!(!is_solvable(&Board::from_string("231.\n")));
+    assert!(!is_solvable(&Board::from_string("2\n3\n1\n.\n")));
+    assert!(!is_solvable(&Board::from_string("21.\n")));
+    assert!(is_solvable(&Board::from_string("1.23\n")));
+    assert!(is_solvable(&Board::from_string(".\n1\n2\n")));
+}
+
+#[test]
+fn test_solve_sizes() {
+    let board = Board::from_string(
+        " 1  2  3  4\n\
+          5  6  .  8\n\
+          9 10  7 11\n\
+         13 14 15 12\n",
+    );
+    for algorithm in [Algorithm::AStar, Algorithm::IdaStar] {
+        let solution = solve_with(board.clone(), &LinearConflict, algorithm).unwrap();
+        assert_eq!(solution.moves.len(), 3);
+        assert_valid_path(&board, &solution.moves);
+    }
+
+    let rectangular = Board::from_string(
+        "5.4\n\
+         132\n",
+    );
+    let expected = solve(rectangular.clone()).unwrap();
+    let solution = solve_with(rectangular.clone(), &Manhattan, Algorithm::IdaStar).unwrap();
+    assert_eq!(solution.moves.len(), expected.len());
+    assert_valid_path(&rectangular, &expected);
+
+    assert_eq!(solve(Board::from_string("21\n3.\n")), None);
+    assert_eq!(
+        solve_with(Board::from_string("21\n3.\n"), &Manhattan, Algorithm::AStar),
+        None
+    );
+}
+
+#[test]
+fn test_parse_board() {
+    assert_eq!(
+        "3.1\n654\n278\n".parse::<Board>(),
+        Ok(Board::from_string("3.1\n654\n278\n"))
+    );
+    assert_eq!(
+        "\n3.1\r\n654\r\n278\n\n".parse::<Board>(),
+        Ok(Board::from_string("3.1\n654\n278\n"))
+    );
+    assert_eq!("".parse::<Board>(), Err(ParseBoardError::Empty));
+    assert_eq!(
+        "123\n45\n78.\n".parse::<Board>(),
+        Err(ParseBoardError::WrongDimensions {
+            line: 2,
+            expected: 3,
+            found: 2
+        })
+    );
+    assert_eq!(
+        "1x2\n345\n678\n".parse::<Board>(),
+        Err(ParseBoardError::BadCharacter {
+            line: 1,
+            column: 2,
+            chr: 'x'
+        })
+    );
+    assert_eq!(
+        "012\n456\n78.\n".parse::<Board>(),
+        Err(ParseBoardError::BadCharacter {
+            line: 1,
+            column: 1,
+            chr: '0'
+        })
+    );
+    assert_eq!(
+        " 1  2  3\n 4 5x  6\n 7  8  .\n".parse::<Board>(),
+        Err(ParseBoardError::BadCharacter {
+            line: 2,
+            column: 5,
+            chr: 'x'
+        })
+    );
+    assert_eq!(
+        "123\n456\n787\n".parse::<Board>(),
+        Err(ParseBoardError::MissingBlank)
+    );
+    assert_eq!(
+        "123\n456\n7.7\n".parse::<Board>(),
+        Err(ParseBoardError::DuplicateTile {
+            line: 3,
+            column: 3,
+            tile: Tile::new(Some(7))
+        })
+    );
+    assert_eq!(
+        "...\n.1.\n...\n".parse::<Board>(),
+        Err(ParseBoardError::DuplicateTile {
+            line: 1,
+            column: 2,
+            tile: Tile::empty()
+        })
+    );
+    assert_eq!(
+        "123\n456\n79.\n".parse::<Board>(),
+        Err(ParseBoardError::TileOutOfRange {
+            line: 3,
+            column: 2,
+            tile: "9".to_string()
+        })
+    );
+    assert_eq!(
+        "1 300\n2 .\n".parse::<Board>(),
+        Err(ParseBoardError::TileOutOfRange {
+            line: 1,
+            column: 3,
+            tile: "300".to_string()
+        })
+    );
+    assert_eq!(
+        ParseBoardError::BadCharacter {
+            line: 1,
+            column: 2,
+            chr: 'x'
+        }
+        .to_string(),
+        "line 1, column 2: unexpected character 'x'"
+    );
+}
+
+#[test]
+fn test_moves() {
+    let board = Board::from_string(
+        "12.\n\
+         453\n\
+         786\n",
+    );
+    assert_eq!(board.apply(Move::Up), None);
+    assert_eq!(board.apply(Move::Right), None);
+    assert_eq!(
+        board.apply(Move::Left),
+        Some(Board::from_string("1.2\n453\n786\n"))
+    );
+    assert_eq!(
+        board.apply(Move::Down).unwrap().apply(Move::Up),
+        Some(board.clone())
+    );
+
+    let path = solve(board.clone()).unwrap();
+    let moves = board.moves_along(&path).unwrap();
+    assert_eq!(moves, vec![Move::Down, Move::Down]);
+    assert_eq!(format_moves(&moves), "DD");
+    assert_eq!(board.moves_along(&path[1..]), None);
+
+    for mv in Move::ALL {
+        assert_eq!(Move::from_letter(mv.letter()), Some(mv));
+        assert_eq!(mv.inverse().inverse(), mv);
+        assert_eq!(mv.to_string(), mv.letter().to_string());
+    }
+}
+
+#[test]
+fn test_replay() {
+    let board = Board::from_string(
+        "321\n\
+         654\n\
+         .78\n",
+    );
+    let path = solve(board.clone()).unwrap();
+    let moves = format_moves(&board.moves_along(&path).unwrap());
+    assert_eq!(moves.len(), 24);
+    assert!(board.replay(&moves).unwrap().is_solved());
+    assert_eq!(board.replay(""), Ok(board.clone()));
+    assert_eq!(board.replay("UR RD\n"), board.replay("URRD"));
+
+    assert_eq!(
+        board.replay("UUX"),
+        Err(ReplayError::UnknownMove {
+            position: 3,
+            chr: 'X'
+        })
+    );
+    assert_eq!(
+        board.replay("UUU"),
+        Err(ReplayError::IllegalMove {
+            position: 3,
+            mv: Move::Up
+        })
+    );
+    assert_eq!(
+        board.replay("L"),
+        Err(ReplayError::IllegalMove {
+            position: 1,
+            mv: Move::Left
+        })
+    );
+}
+
+#[test]
+fn test_pattern_database_exact() {
+    // A single pattern with every tile gives the exact distance.
+    let pdb = PatternDatabase::build(2, 3, &[vec![1, 2, 3, 4, 5]]).unwrap();
+    assert_eq!(pdb.size(), (2, 3));
+    for input in ["5.4\n132\n", "123\n45.\n", "431\n.52\n", "12.\n453\n"] {
+        let board = Board::from_string(input);
+        let expected = solve(board.clone()).unwrap();
+        assert_eq!(pdb.estimate(&board) as usize, expected.len());
+    }
+}
+
+#[test]
+fn test_pattern_database_additive() {
+    let pdb = PatternDatabase::build(3, 3, &PatternDatabase::default_patterns(3, 3, 4)).unwrap();
+    assert_eq!(pdb.patterns(), vec![vec![1, 2, 3, 4], vec![5, 6, 7, 8]]);
+    assert_eq!(pdb.estimate(&Board::solved(3, 3)), 0);
+
+    for input in ["321\n654\n.78\n", "8.6\n547\n231\n", "647\n85.\n321\n"] {
+        let board = Board::from_string(input);
+        let expected = solve(board.clone()).unwrap().len() as u32;
+        let estimate = pdb.estimate(&board);
+        assert!(estimate <= expected);
+        assert!(estimate >= Manhattan.estimate(&board));
+
+        for algorithm in [Algorithm::AStar, Algorithm::IdaStar] {
+            let solution = solve_with(board.clone(), &pdb, algorithm).unwrap();
+            assert_eq!(solution.moves.len() as u32, expected);
+            assert_valid_path(&board, &solution.moves);
+        }
+    }
+}
+
+#[test]
+fn test_pattern_database_serialization() {
+    let pdb = PatternDatabase::build(3, 3, &[vec![1, 2, 5], vec![3, 6]]).unwrap();
+    let mut buffer = Vec::new();
+    pdb.write_to(&mut buffer).unwrap();
+    assert_eq!(buffer.len(), 6 + 4 + (1 + 3 + 9 * 8 * 7) + (1 + 2 + 9 * 8));
+    assert_eq!(PatternDatabase::read_from(buffer.as_slice()).unwrap(), pdb);
+
+    assert!(matches!(
+        PatternDatabase::read_from(&buffer[..buffer.len() - 1]),
+        Err(PdbError::Corrupted)
+    ));
+    assert!(matches!(
+        PatternDatabase::read_from(&b"garbage"[..]),
+        Err(PdbError::BadMagic)
+    ));
+    buffer.push(0);
+    assert!(matches!(
+        PatternDatabase::read_from(buffer.as_slice()),
+        Err(PdbError::Corrupted)
+    ));
+
+    // Headers of 16x16 databases whose tables would not fit into memory.
+    for len in [5, 8] {
+        let mut header = b"PZPDB\0\x01\x0f\x0f\x01".to_vec();
+        header.push(len);
+        header.extend(1..=len);
+        assert!(matches!(
+            PatternDatabase::read_from(header.as_slice()),
+            Err(PdbError::Corrupted)
+        ));
+    }
+}
+
+#[test]
+fn test_pattern_database_invalid() {
+    assert!(matches!(
+        PatternDatabase::build(3, 3, &[vec![1, 2], vec![2, 3]]),
+        Err(PdbError::DuplicateTile(2))
+    ));
+    assert!(matches!(
+        PatternDatabase::build(3, 3, &[vec![1, 9]]),
+        Err(PdbError::TileOutOfRange(9))
+    ));
+    assert!(matches!(
+        PatternDatabase::build(3, 3, &[vec![]]),
+        Err(PdbError::EmptyPattern)
+    ));
+    // 16 * 16! / 8! entries, about 8 GB.
+    assert!(matches!(
+        PatternDatabase::build(4, 4, &[(1..=8).collect()]),
+        Err(PdbError::TableTooLarge(8))
+    ));
+    assert!(matches!(
+        PatternDatabase::build(16, 16, &[(1..=8).collect()]),
+        Err(PdbError::TableTooLarge(8))
+    ));
+}
+
+#[test]
+fn test_parse_error_offset_lines() {
+    let err = "123\n4x6\n78.\n".parse::<Board>().unwrap_err();
+    assert_eq!(
+        err.offset_lines(10),
+        ParseBoardError::BadCharacter {
+            line: 12,
+            column: 2,
+            chr: 'x'
+        }
+    );
+    assert_eq!(
+        ParseBoardError::MissingBlank.offset_lines(10),
+        ParseBoardError::MissingBlank
+    );
+}
+
+#[test]
+fn test_random_board() {
+    let first = random_board(4, 4, &mut StdRng::seed_from_u64(42));
+    let second = random_board(4, 4, &mut StdRng::seed_from_u64(42));
+    assert_eq!(first, second);
+
+    let mut rng = StdRng::seed_from_u64(1);
+    for (rows, cols) in [(2, 2), (2, 3), (3, 3), (3, 4), (4, 4), (5, 5), (1, 5), (4, 1)] {
+        for _ in 0..20 {
+            let board = random_board(rows, cols, &mut rng);
+            assert_eq!(board.size(), (rows, cols));
+            assert!(is_solvable(&board), "unsolvable board:\n{}", board);
+        }
+    }
+
+    for _ in 0..10 {
+        let board = random_board(3, 3, &mut rng);
+        let bfs = solve(board.clone()).unwrap();
+        let ida = solve_with(board.clone(), &LinearConflict, Algorithm::IdaStar).unwrap();
+        assert_valid_path(&board, &ida.moves);
+        assert_eq!(bfs.len(), ida.moves.len());
+    }
+}
+
+#[test]
+fn test_board_at_distance() {
+    let mut rng = StdRng::seed_from_u64(7);
+    for distance in [0, 1, 5, 12, 31] {
+        let board = board_at_distance(3, 3, distance, &mut rng).unwrap();
+        assert_eq!(solve(board).unwrap().len(), distance);
+    }
+    assert_eq!(board_at_distance(3, 3, 32, &mut rng), None);
+    assert_eq!(board_at_distance(2, 2, 7, &mut rng), None);
+
+    let board = board_at_distance(1, 4, 3, &mut rng).unwrap();
+    assert_eq!(board, Board::from_string(".123\n"));
+
+    let board = board_at_distance(4, 4, 10, &mut rng).unwrap();
+    let solution = solve_with(board, &LinearConflict, Algorithm::IdaStar).unwrap();
+    assert_eq!(solution.moves.len(), 10);
+}
+
+#[test]
+fn test_grade() {
+    assert_eq!(
+        grade(&Board::solved(3, 3), &LinearConflict),
+        Some(Grade {
+            length: 0,
+            difficulty: Some(Difficulty::Easy)
+        })
+    );
+    assert_eq!(
+        grade(&Board::from_string("867\n254\n3.1\n"), &LinearConflict),
+        Some(Grade {
+            length: 31,
+            difficulty: Some(Difficulty::Expert)
+        })
+    );
+    assert_eq!(
+        grade(&Board::from_string("123\n456\n87.\n"), &Manhattan),
+        None
+    );
+    assert_eq!(
+        grade(&Board::solved(5, 5), &Manhattan).unwrap().difficulty,
+        None
+    );
+
+    assert_eq!(Difficulty::from_length(7, 3, 3), Some(Difficulty::Easy));
+    assert_eq!(Difficulty::from_length(8, 3, 3), Some(Difficulty::Medium));
+    assert_eq!(Difficulty::from_length(16, 3, 3), Some(Difficulty::Hard));
+    assert_eq!(Difficulty::from_length(24, 3, 3), Some(Difficulty::Expert));
+    assert_eq!(Difficulty::from_length(40, 4, 4), Some(Difficulty::Hard));
+    assert_eq!(Difficulty::Medium.name(), "medium");
+}
+
+#[test]
+fn test_solve_to() {
+    let blank_first = Board::from_string(".12\n345\n678\n");
+    let spiral = Board::from_string("123\n8.4\n765\n");
+
+    let start = Board::from_string("1.2\n345\n678\n");
+    assert_eq!(
+        solve_to(start.clone(), &blank_first),
+        Some(vec![blank_first.clone()])
+    );
+    assert_eq!(solve_to(blank_first.clone(), &blank_first), Some(vec![]));
+
+    let mut rng = StdRng::seed_from_u64(3);
+    for goal in [&blank_first, &spiral] {
+        for _ in 0..10 {
+            let start = random_board(3, 3, &mut rng);
+            match solve_to(start.clone(), goal) {
+                Some(path) => {
+                    assert!(is_solvable_to(&start, goal));
+                    assert_eq!(path.last().unwrap_or(&start), goal);
+                    let mut current = &start;
+                    for next in path.iter() {
+                        assert!(current.neighbours().contains(next));
+                        current = next;
+                    }
+                }
+                None => assert!(!is_solvable_to(&start, goal)),
+            }
+        }
+    }
+
+    // The canonical goal is an odd permutation away from the spiral one.
+    assert!(!is_solvable_to(&Board::solved(3, 3), &spiral));
+    assert!(is_solvable_to(&Board::solved(3, 3), &blank_first));
+    assert_eq!(solve_to(Board::solved(3, 3), &spiral), None);
+    assert_eq!(
+        solve_to(Board::solved(3, 3), &blank_first).unwrap().len(),
+        solve(blank_first.clone()).unwrap().len()
+    );
+}
+
+#[test]
+fn test_is_solvable_to() {
+    let mut rng = StdRng::seed_from_u64(11);
+    for (rows, cols) in [(2, 2), (2, 3), (3, 2), (3, 3)] {
+        for _ in 0..10 {
+            let start = random_board(rows, cols, &mut rng);
+            let mut goal = random_board(rows, cols, &mut rng);
+            assert!(is_solvable_to(&start, &goal));
+            assert!(solve_to(start.clone(), &goal).is_some());
+
+            let values = goal.values();
+            let mut numbered = (0..rows * cols).filter(|&i| values[i] != 0);
+            let (a, b) = (numbered.next().unwrap(), numbered.next().unwrap());
+            goal.swap(a / cols, a % cols, b / cols, b % cols);
+            assert!(!is_solvable_to(&start, &goal));
+            assert!(!is_solvable_to(&goal, &start));
+        }
+    }
+
+    assert!(is_solvable_to(
+        &Board::from_string("1.23\n"),
+        &Board::from_string("123.\n")
+    ));
+    assert!(!is_solvable_to(
+        &Board::from_string("2.13\n"),
+        &Board::from_string("123.\n")
+    ));
+    assert!(!is_solvable_to(&Board::solved(2, 3), &Board::solved(3, 2)));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+
+fn run_puzzle(args: &[&str], input: &str) -> std::process::Output {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_puzzle"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+// The first board takes much longer than the others, so workers finish out of order.
+const BATCH_INPUT: &str =
+    "876\n543\n21.\n\n123\n456\n87.\n\n\n12x\n456\n78.\n\n1.\n32\n\n.12\n453\n786\n";
+
+/// Line of the output, start board and solution length of every solvable board.
+const BATCH_SOLVED: [(usize, &str, usize); 3] = [
+    (0, "876\n543\n21.\n", 30),
+    (3, "1.\n32\n", 1),
+    (4, ".12\n453\n786\n", 4),
+];
+
+/// Splits a line like `#1 solved length=30 time=1.234ms moves=...` into its fields.
+fn batch_fields(line: &str) -> Vec<(&str, &str)> {
+    line.split(' ')
+        .skip(2)
+        .map(|field| field.split_once('=').unwrap())
+        .collect()
+}
+
+#[test]
+fn test_batch_moves() {
+    for threads in ["1", "2", "4"] {
+        let output = run_puzzle(
+            &["batch", "--format", "moves", "--threads", threads],
+            BATCH_INPUT,
+        );
+        // Malformed boards make the exit status 1.
+        assert_eq!(output.status.code(), Some(1), "threads {}", threads);
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let lines = stdout.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 6, "threads {}: {}", threads, stdout);
+
+        for (index, &line) in lines[..5].iter().enumerate() {
+            let prefix = format!("#{} ", index + 1);
+            assert!(line.starts_with(&prefix), "threads {}: {}", threads, line);
+        }
+        assert!(lines[1].starts_with("#2 unsolvable time="));
+        assert_eq!(
+            lines[2],
+            "#3 malformed: line 10, column 3: unexpected character 'x'"
+        );
+        assert_eq!(lines[5], "solved: 3, unsolvable: 1, malformed: 1");
+
+        for (index, start, expected) in BATCH_SOLVED {
+            let fields = batch_fields(lines[index]);
+            let [("length", length), ("time", time), ("moves", moves)] = fields[..] else {
+                panic!("unexpected line {}", lines[index]);
+            };
+            assert_eq!(length.parse::<usize>().unwrap(), expected);
+            assert_eq!(moves.len(), expected);
+            assert!(time.strip_suffix("ms").unwrap().parse::<f64>().unwrap() >= 0.0);
+            assert!(Board::from_string(start).replay(moves).unwrap().is_solved());
+        }
+    }
+}
+
+#[test]
+fn test_batch_json() {
+    let output = run_puzzle(
+        &["batch", "--format", "json", "--threads", "3"],
+        BATCH_INPUT,
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 6);
+    assert!(lines[0].starts_with(r#"{"index":1,"status":"solved","length":30,"time_ms":"#));
+    assert!(lines[1].starts_with(r#"{"index":2,"status":"unsolvable","time_ms":"#));
+    assert_eq!(
+        lines[2],
+        r#"{"index":3,"status":"malformed","error":"line 10, column 3: unexpected character 'x'"}"#
+    );
+    assert!(lines[3].starts_with(r#"{"index":4,"status":"solved","length":1,"#));
+    assert!(lines[3].ends_with(r#""moves":"D"}"#));
+    assert!(lines[4].ends_with(r#""moves":"RRDD"}"#));
+    assert_eq!(
+        lines[5],
+        r#"{"summary":{"solved":3,"unsolvable":1,"malformed":1}}"#
+    );
+}
+
+#[test]
+fn test_batch_boards() {
+    let input = "123\n456\n7.8\n\n1.\n32\n";
+    for threads in ["1", "2"] {
+        let output = run_puzzle(&["batch", "--threads", threads], input);
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let lines = stdout.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 10, "{}", stdout);
+        assert!(lines[0].starts_with("#1 solved length=1 time="));
+        assert_eq!(lines[1..5], ["---", "123", "456", "78."]);
+        assert!(lines[5].starts_with("#2 solved length=1 time="));
+        assert_eq!(lines[6..9], ["---", "12", "3."]);
+        assert_eq!(lines[9], "solved: 2, unsolvable: 0, malformed: 0");
+    }
+}